@@ -4,7 +4,6 @@ use clap::Parser;
 use std::net::SocketAddr;
 
 use tracing::{error, info};
-use tracing_subscriber;
 
 use beacon_probe_cmd::cli::Cli;
 use beacon_probe_echo::server::serve_echo;
@@ -1,59 +1,244 @@
 #![allow(missing_docs)]
 
 use clap::Parser;
+use std::io::IsTerminal;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use tracing::{error, info};
-use tracing_subscriber;
+use futures_util::future::try_join_all;
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{error, info, info_span, Instrument, Level};
 
 use blockspeed_cmd::cli::Cli;
 use blockspeed_echo::server::serve_echo;
+use blockspeed_trace::buffer::BufferedSink;
+use blockspeed_trace::config::{Endpoint, TraceConfig};
+use blockspeed_trace::file::FileSink;
+use blockspeed_trace::line_protocol::LineProtocolSink;
 use blockspeed_trace::output::Output;
+use blockspeed_trace::prometheus::PrometheusSink;
+use blockspeed_trace::propagation::PropagationTracker;
+use blockspeed_trace::report::ReportSink;
+use blockspeed_trace::sink::{MetricsSink, MultiSink, SinkKind};
+use blockspeed_trace::status::{HealthThresholds, StatusRegistry};
+use blockspeed_trace::tui::TuiSink;
 use blockspeed_trace::ws::WsConnection;
 use eth_kit_metrics::{recorder::install_prometheus_recorder, server::MetricsServer};
 
 #[tokio::main]
 pub async fn main() -> eyre::Result<()> {
-    tracing_subscriber::fmt::init();
-
     let cli = Cli::parse();
+    let tui = cli.resolve_tui().unwrap();
+
+    if tui {
+        // keep stdout for the TUI, logs can be redirected with 2>. On the same terminal only
+        // errors get through, reconnect and warning lines would garble the view.
+        let max_level = if std::io::stderr().is_terminal() {
+            Level::ERROR
+        } else {
+            Level::INFO
+        };
+        tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .with_max_level(max_level)
+            .init();
+    } else {
+        tracing_subscriber::fmt::init();
+    }
 
     let addr = cli.resolve_addr().unwrap();
     let metrics_addr = cli.resolve_metrics_addr().unwrap();
-    // let http_rpc_url = cli.resolve_http_rpc_url().unwrap();
-    let ws_rpc_url = cli.resolve_ws_rpc_url().unwrap();
-    let influxdb_host = cli.resolve_influxdb_host().unwrap();
-    let influxdb_org = cli.resolve_influxdb_org().unwrap();
-    let influxdb_token = cli.resolve_influxdb_token().unwrap();
-    let influxdb_bucket = cli.resolve_influxdb_bucket().unwrap();
-
-    let output = Arc::new(connect_influxdb(
-        &influxdb_host,
-        &influxdb_token,
-        &influxdb_org,
-        &influxdb_bucket,
-    )?);
-
-    let ws_connection = WsConnection::new(&ws_rpc_url.as_str())
-        .await
-        .expect("failed to connect to WebSocket");
-
-    if let Err(e) = tokio::try_join!(
-        serve_app(addr),
-        serve_metrics(metrics_addr),
-        subscribe_to_head(&ws_connection, Arc::clone(&output))
-    ) {
-        error!("error: {:?}", e);
+    let replay_range = cli.resolve_replay_range().unwrap();
+    let trace_config = cli.resolve_trace_config().unwrap();
+    let sink_kinds = cli.resolve_sinks().unwrap();
+    let buffer_config = cli.resolve_buffer_config().unwrap();
+    let schema = cli.resolve_schema().unwrap();
+    let health_thresholds = cli.resolve_health_thresholds().unwrap();
+    let report = cli.resolve_report().unwrap();
+    let report_json = cli.resolve_report_json().unwrap();
+    let duration = cli.resolve_duration().unwrap();
+
+    // install the recorder up front so metric descriptions aren't lost to the no-op recorder
+    install_prometheus_recorder();
+
+    let status = Arc::new(StatusRegistry::new());
+
+    let mut sinks: Vec<Arc<dyn MetricsSink>> = Vec::with_capacity(sink_kinds.len());
+    for sink_kind in sink_kinds {
+        match sink_kind {
+            SinkKind::InfluxDb => {
+                let influxdb_host = cli.resolve_influxdb_host().unwrap();
+                let influxdb_org = cli.resolve_influxdb_org().unwrap();
+                let influxdb_token = cli.resolve_influxdb_token().unwrap();
+                let influxdb_bucket = cli.resolve_influxdb_bucket().unwrap();
+
+                let mut output = connect_influxdb(
+                    &influxdb_host,
+                    &influxdb_token,
+                    &influxdb_org,
+                    &influxdb_bucket,
+                )?
+                .with_schema(schema);
+                if let Some(spool) = cli.resolve_spool().unwrap() {
+                    output = output.with_spool(spool);
+                }
+                // a slow InfluxDB must not hold up header processing
+                sinks.push(Arc::new(BufferedSink::new(
+                    "influxdb",
+                    Arc::new(output),
+                    buffer_config.clone(),
+                    Arc::clone(&status),
+                )));
+            }
+            SinkKind::Prometheus => sinks.push(Arc::new(PrometheusSink::new())),
+            SinkKind::File => {
+                let path = cli.resolve_file_path().unwrap();
+                let format = cli.resolve_file_format().unwrap();
+                let rotation = cli.resolve_file_rotation().unwrap();
+
+                let file = FileSink::new(&path, format, rotation)?;
+                sinks.push(Arc::new(BufferedSink::new(
+                    "file",
+                    Arc::new(file),
+                    buffer_config.clone(),
+                    Arc::clone(&status),
+                )));
+            }
+            SinkKind::LineProtocol => {
+                let url = cli.resolve_line_protocol_url().unwrap();
+                let options = cli.resolve_line_protocol_options().unwrap();
+
+                let line_protocol = LineProtocolSink::new(&url, options)?.with_schema(schema);
+                sinks.push(Arc::new(BufferedSink::new(
+                    "line-protocol",
+                    Arc::new(line_protocol),
+                    buffer_config.clone(),
+                    Arc::clone(&status),
+                )));
+            }
+        }
+    }
+    let tui_sink = tui.then(|| Arc::new(TuiSink::new()));
+    if let Some(tui_sink) = &tui_sink {
+        sinks.push(Arc::clone(tui_sink) as Arc<dyn MetricsSink>);
+    }
+    let report_sink = report.then(|| Arc::new(ReportSink::new()));
+    if let Some(report_sink) = &report_sink {
+        sinks.push(Arc::clone(report_sink) as Arc<dyn MetricsSink>);
+    }
+    let sink: Arc<dyn MetricsSink> = if sinks.len() == 1 {
+        sinks.remove(0)
     } else {
-        info!("shutting down");
+        Arc::new(MultiSink::new(sinks))
+    };
+
+    if let Some((from, to)) = replay_range {
+        let endpoints = cli.resolve_replay_endpoints().unwrap();
+        let result = tokio::select! {
+            result = replay(endpoints, &trace_config, from, to, Arc::clone(&sink)) => result,
+            result = shutdown_signal() => result,
+            _ = stop_after(duration) => {
+                info!("duration elapsed, stopping replay");
+                Ok(())
+            }
+        };
+        // whatever was computed before a failure or interruption is still worth writing
+        if let Err(e) = sink.flush().await {
+            error!("failed to flush sinks: {:?}", e);
+        }
+        write_report(report_sink.as_deref(), report_json.as_deref());
+        return result;
+    }
+
+    let endpoints = cli.resolve_endpoints().unwrap();
+
+    // propagation delay only makes sense when there is something to compare against
+    let propagation = (endpoints.len() > 1).then(|| Arc::new(PropagationTracker::new()));
+
+    let mut ws_connections = Vec::with_capacity(endpoints.len());
+    for endpoint in endpoints {
+        let mut ws_connection = WsConnection::new(endpoint, trace_config.clone())
+            .await
+            .expect("failed to connect to WebSocket")
+            .with_status(Arc::clone(&status));
+        if let Some(propagation) = &propagation {
+            ws_connection = ws_connection.with_propagation(Arc::clone(propagation));
+        }
+        ws_connections.push(ws_connection);
+    }
+
+    let run = async {
+        tokio::try_join!(
+            serve_app(addr, Arc::clone(&status), health_thresholds),
+            serve_metrics(metrics_addr),
+            subscribe_to_heads(&ws_connections, Arc::clone(&sink)),
+            render_tui(tui_sink.as_deref())
+        )
+    };
+    tokio::select! {
+        result = run => match result {
+            Err(e) => error!("error: {:?}", e),
+            Ok(_) => info!("shutting down"),
+        },
+        _ = stop_after(duration) => {
+            info!("duration elapsed, shutting down");
+            if tui_sink.is_some() {
+                // the TUI was cancelled mid-frame rather than stopped by a signal
+                let _ = TuiSink::restore();
+            }
+        }
+    }
+
+    // buffered sinks still hold up to a batch, whichever way the run ended
+    if let Err(e) = sink.flush().await {
+        error!("failed to flush sinks: {:?}", e);
     }
+    write_report(report_sink.as_deref(), report_json.as_deref());
 
     Ok(())
 }
 
-pub async fn serve_app(addr: SocketAddr) -> eyre::Result<()> {
-    serve_echo(addr).await?;
+/// Completes on SIGINT or SIGTERM.
+pub async fn shutdown_signal() -> eyre::Result<()> {
+    let mut sigint = signal(SignalKind::interrupt())?;
+    let mut sigterm = signal(SignalKind::terminate())?;
+    tokio::select! {
+        _ = sigint.recv() => info!("received sigint, shutting down"),
+        _ = sigterm.recv() => info!("received sigterm, shutting down"),
+    }
+    Ok(())
+}
+
+/// Never completes without a duration.
+pub async fn stop_after(duration: Option<Duration>) {
+    match duration {
+        Some(duration) => tokio::time::sleep(duration).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Prints the end-of-run report to stdout and writes it as JSON if asked to.
+pub fn write_report(report_sink: Option<&ReportSink>, json_path: Option<&str>) {
+    let Some(report_sink) = report_sink else {
+        return;
+    };
+    let report = report_sink.report();
+    println!("{}", report.to_table());
+    if let Some(path) = json_path {
+        match report.write_json(path) {
+            Ok(()) => info!("wrote report to {}", path),
+            Err(e) => error!("{}", e),
+        }
+    }
+}
+
+pub async fn serve_app(
+    addr: SocketAddr,
+    status: Arc<StatusRegistry>,
+    thresholds: HealthThresholds,
+) -> eyre::Result<()> {
+    serve_echo(addr, status, thresholds).await?;
     Ok(())
 }
 
@@ -63,6 +248,33 @@ pub async fn serve_metrics(addr: SocketAddr) -> eyre::Result<()> {
     Ok(())
 }
 
+pub async fn replay(
+    endpoints: Vec<Endpoint>,
+    trace_config: &TraceConfig,
+    from: u64,
+    to: u64,
+    sink: Arc<dyn MetricsSink>,
+) -> eyre::Result<()> {
+    try_join_all(endpoints.into_iter().map(|endpoint| {
+        let sink = Arc::clone(&sink);
+        let name = endpoint.name.clone();
+        async move {
+            let ws_connection = WsConnection::new(endpoint, trace_config.clone()).await?;
+            ws_connection.replay(from, to, sink).await
+        }
+        .instrument(info_span!("replay", endpoint = name))
+    }))
+    .await?;
+    Ok(())
+}
+
+pub async fn render_tui(tui_sink: Option<&TuiSink>) -> eyre::Result<()> {
+    if let Some(tui_sink) = tui_sink {
+        tui_sink.run(Duration::from_millis(500)).await?;
+    }
+    Ok(())
+}
+
 pub fn connect_influxdb(host: &str, token: &str, org: &str, bucket: &str) -> eyre::Result<Output> {
     let client = Output::new(host, token, org, bucket);
     Ok(client)
@@ -70,11 +282,23 @@ pub fn connect_influxdb(host: &str, token: &str, org: &str, bucket: &str) -> eyr
 
 pub async fn subscribe_to_head(
     ws_connection: &WsConnection,
-    output: Arc<Output>,
+    sink: Arc<dyn MetricsSink>,
 ) -> eyre::Result<()> {
     ws_connection
-        .subscribe_to_head(output)
+        .subscribe_to_head(sink)
         .await
         .map_err(|e| eyre::eyre!("failed to subscribe to head: {}", e))?;
     Ok(())
 }
+
+pub async fn subscribe_to_heads(
+    ws_connections: &[WsConnection],
+    sink: Arc<dyn MetricsSink>,
+) -> eyre::Result<()> {
+    try_join_all(ws_connections.iter().map(|ws_connection| {
+        subscribe_to_head(ws_connection, Arc::clone(&sink))
+            .instrument(info_span!("ws", endpoint = ws_connection.name()))
+    }))
+    .await?;
+    Ok(())
+}
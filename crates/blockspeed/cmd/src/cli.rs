@@ -1,12 +1,27 @@
 #![allow(missing_docs)]
 
-use clap::Parser;
+use blockspeed_trace::block::BlockFetch;
+use blockspeed_trace::buffer::BufferConfig;
+use blockspeed_trace::config::{load_endpoints, Endpoint, TraceConfig};
+use blockspeed_trace::file::{FileFormat, Rotation};
+use blockspeed_trace::l2::Chain;
+use blockspeed_trace::line_protocol::V1Options;
+use blockspeed_trace::output::Schema;
+use blockspeed_trace::reconnect::ReconnectPolicy;
+use blockspeed_trace::sink::SinkKind;
+use blockspeed_trace::spool::Spool;
+use blockspeed_trace::status::HealthThresholds;
+use blockspeed_trace::throughput::{parse_window, ThroughputClock};
+use clap::{Args, Parser, Subcommand};
 use std::net::{SocketAddr, ToSocketAddrs};
-use url::Url;
+use std::time::Duration;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     #[arg(
         short,
         long,
@@ -38,17 +53,278 @@ pub struct Cli {
     #[arg(long, value_name = "WS_RPC_URL", env = "WS_RPC_URL")]
     ws_rpc_url: Option<String>,
 
+    /// polled when the `--ws-rpc-url` subscription keeps failing
+    #[arg(long, value_name = "HTTP_RPC_URL", env = "HTTP_RPC_URL")]
+    http_rpc_url: Option<String>,
+
+    #[arg(
+        long = "endpoint",
+        value_name = "NAME=WS_RPC_URL",
+        env = "ENDPOINTS",
+        value_delimiter = ','
+    )]
+    endpoints: Vec<String>,
+
+    #[arg(long, value_name = "ENDPOINTS_FILE", env = "ENDPOINTS_FILE")]
+    endpoints_file: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "CHAIN",
+        env = "CHAIN",
+        default_value = "l1",
+        value_parser = ["l1", "op-stack", "arbitrum"]
+    )]
+    chain: String,
+
+    #[arg(
+        long,
+        value_name = "BLOCK_FETCH",
+        env = "BLOCK_FETCH",
+        default_value = "header",
+        value_parser = ["header", "full", "receipts"]
+    )]
+    block_fetch: String,
+
+    #[arg(
+        long,
+        value_name = "THROUGHPUT_CLOCK",
+        env = "THROUGHPUT_CLOCK",
+        default_value = "arrival",
+        value_parser = ["arrival", "block"]
+    )]
+    throughput_clock: String,
+
+    #[arg(
+        long,
+        value_name = "ROLLING_WINDOWS",
+        env = "ROLLING_WINDOWS",
+        default_value = "1m,5m,1h",
+        value_delimiter = ','
+    )]
+    rolling_windows: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "POLL_INTERVAL_MS",
+        env = "POLL_INTERVAL_MS",
+        default_value = "1000"
+    )]
+    poll_interval_ms: u64,
+
+    #[arg(
+        long,
+        value_name = "RECONNECT_POLICY",
+        env = "RECONNECT_POLICY",
+        default_value = "unlimited",
+        value_parser = ["unlimited", "circuit-breaker"]
+    )]
+    reconnect_policy: String,
+
+    #[arg(
+        long,
+        value_name = "RECONNECT_MAX_BACKOFF_MS",
+        env = "RECONNECT_MAX_BACKOFF_MS",
+        default_value = "30000"
+    )]
+    reconnect_max_backoff_ms: u64,
+
+    #[arg(
+        long,
+        value_name = "CIRCUIT_BREAKER_FAILURES",
+        env = "CIRCUIT_BREAKER_FAILURES",
+        default_value = "10"
+    )]
+    circuit_breaker_failures: u32,
+
+    #[arg(
+        long,
+        value_name = "CIRCUIT_BREAKER_COOLDOWN",
+        env = "CIRCUIT_BREAKER_COOLDOWN",
+        default_value = "5m"
+    )]
+    circuit_breaker_cooldown: String,
+
+    #[arg(
+        long,
+        value_name = "HEALTH_MAX_HEAD_AGE",
+        env = "HEALTH_MAX_HEAD_AGE",
+        default_value = "2m"
+    )]
+    health_max_head_age: String,
+
+    #[arg(
+        long,
+        value_name = "HEALTH_MAX_SINK_ERROR_RATE",
+        env = "HEALTH_MAX_SINK_ERROR_RATE",
+        default_value = "0.5"
+    )]
+    health_max_sink_error_rate: f64,
+
+    #[arg(
+        long,
+        value_name = "BLOB_BASE_FEE_UPDATE_FRACTION",
+        env = "BLOB_BASE_FEE_UPDATE_FRACTION",
+        default_value = "5007716"
+    )]
+    blob_base_fee_update_fraction: u64,
+
+    #[arg(
+        long,
+        value_name = "MAX_BACKFILL",
+        env = "MAX_BACKFILL",
+        default_value = "64"
+    )]
+    max_backfill: u64,
+
+    #[arg(
+        long = "sink",
+        value_name = "SINK",
+        env = "SINKS",
+        value_delimiter = ',',
+        value_parser = ["influxdb", "prometheus", "line-protocol", "file"]
+    )]
+    sinks: Vec<String>,
+
+    /// render a live terminal view, only errors are logged unless stderr is redirected. Sinks
+    /// become optional
+    #[arg(long, env = "TUI")]
+    tui: bool,
+
+    /// subscribe to `newPendingTransactions` to measure inclusion latency and mempool inflow
+    #[arg(long, env = "PENDING_TXS")]
+    pending_txs: bool,
+
+    /// print a percentile summary per endpoint when the run ends, sinks become optional
+    #[arg(long, env = "REPORT")]
+    report: bool,
+
+    /// also write the end-of-run summary as JSON to this path
+    #[arg(long, value_name = "REPORT_JSON", env = "REPORT_JSON")]
+    report_json: Option<String>,
+
+    /// stop after this long (`s`, `m`, `h`), e.g. to end a load test
+    #[arg(long, value_name = "DURATION", env = "DURATION")]
+    duration: Option<String>,
+
+    /// write the pre-`blockspeed_block` per-value measurements instead
+    #[arg(long, env = "LEGACY_SCHEMA")]
+    legacy_schema: bool,
+
+    #[arg(
+        long,
+        value_name = "SINK_BUFFER_CAPACITY",
+        env = "SINK_BUFFER_CAPACITY",
+        default_value = "10000"
+    )]
+    sink_buffer_capacity: usize,
+
+    #[arg(
+        long,
+        value_name = "SINK_BATCH_SIZE",
+        env = "SINK_BATCH_SIZE",
+        default_value = "100"
+    )]
+    sink_batch_size: usize,
+
+    #[arg(
+        long,
+        value_name = "SINK_FLUSH_INTERVAL_MS",
+        env = "SINK_FLUSH_INTERVAL_MS",
+        default_value = "1000"
+    )]
+    sink_flush_interval_ms: u64,
+
+    #[arg(long, value_name = "FILE_PATH", env = "FILE_PATH")]
+    file_path: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE_FORMAT",
+        env = "FILE_FORMAT",
+        default_value = "csv",
+        value_parser = ["csv", "jsonl"]
+    )]
+    file_format: String,
+
+    #[arg(
+        long,
+        value_name = "FILE_MAX_BYTES",
+        env = "FILE_MAX_BYTES",
+        default_value = "104857600"
+    )]
+    file_max_bytes: u64,
+
+    #[arg(
+        long,
+        value_name = "FILE_ROTATE_INTERVAL",
+        env = "FILE_ROTATE_INTERVAL"
+    )]
+    file_rotate_interval: Option<String>,
+
+    #[arg(long, value_name = "SPOOL_PATH", env = "SPOOL_PATH")]
+    spool_path: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "SPOOL_MAX_BYTES",
+        env = "SPOOL_MAX_BYTES",
+        default_value = "67108864"
+    )]
+    spool_max_bytes: u64,
+
+    #[arg(long, value_name = "LINE_PROTOCOL_URL", env = "LINE_PROTOCOL_URL")]
+    line_protocol_url: Option<String>,
+
+    #[arg(long, value_name = "LINE_PROTOCOL_DB", env = "LINE_PROTOCOL_DB")]
+    line_protocol_db: Option<String>,
+
+    #[arg(long, value_name = "LINE_PROTOCOL_RP", env = "LINE_PROTOCOL_RP")]
+    line_protocol_rp: Option<String>,
+
+    #[arg(long, value_name = "LINE_PROTOCOL_USER", env = "LINE_PROTOCOL_USER")]
+    line_protocol_user: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "LINE_PROTOCOL_PASSWORD",
+        env = "LINE_PROTOCOL_PASSWORD"
+    )]
+    line_protocol_password: Option<String>,
+
     #[arg(long, value_name = "INFLUXDB_HOST", env = "INFLUXDB_HOST")]
-    influxdb_host: String,
+    influxdb_host: Option<String>,
 
     #[arg(long, value_name = "INFLUXDB_ORG", env = "INFLUXDB_ORG")]
-    influxdb_org: String,
+    influxdb_org: Option<String>,
 
     #[arg(long, value_name = "INFLUXDB_TOKEN", env = "INFLUXDB_TOKEN")]
-    influxdb_token: String,
+    influxdb_token: Option<String>,
 
     #[arg(long, value_name = "INFLUXDB_BUCKET", env = "INFLUXDB_BUCKET")]
-    influxdb_bucket: String,
+    influxdb_bucket: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Compute metrics for a historical block range and write them to the sinks
+    Replay(ReplayArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ReplayArgs {
+    /// first block of the range
+    #[arg(long)]
+    from: u64,
+
+    /// last block of the range, inclusive
+    #[arg(long)]
+    to: u64,
+
+    /// HTTP or WS endpoint to replay from (`name=url` or `url`), defaults to the configured
+    /// endpoints
+    #[arg(long, value_name = "RPC_URL", env = "REPLAY_RPC_URL")]
+    rpc_url: Option<String>,
 }
 
 impl Cli {
@@ -70,50 +346,295 @@ impl Cli {
             .ok_or_else(|| eyre::eyre!("unable to resolve address: {}", addr_str))
     }
 
-    pub fn resolve_ws_rpc_url(&self) -> eyre::Result<Url> {
-        match self.ws_rpc_url.as_deref() {
-            Some(url_str) => match Url::parse(url_str) {
-                Ok(url) => {
-                    if url.scheme() == "ws" || url.scheme() == "wss" {
-                        Ok(url)
-                    } else {
-                        Err(eyre::eyre!("invalid WS RPC URL scheme: {}", url))
-                    }
+    pub fn resolve_endpoints(&self) -> eyre::Result<Vec<Endpoint>> {
+        let mut endpoints = Vec::new();
+
+        if let Some(url_str) = self.ws_rpc_url.as_deref() {
+            let mut endpoint = url_str.parse::<Endpoint>()?;
+            if let Some(http_rpc_url) = self.resolve_http_rpc_url()? {
+                endpoint = endpoint.with_http_url(&http_rpc_url)?;
+            }
+            endpoints.push(endpoint);
+        } else if let Some(http_rpc_url) = self.resolve_http_rpc_url()? {
+            // without a WebSocket URL the HTTP endpoint is polled on its own
+            endpoints.push(http_rpc_url.parse::<Endpoint>()?);
+        }
+        for endpoint in &self.endpoints {
+            endpoints.push(endpoint.parse::<Endpoint>()?);
+        }
+        if let Some(path) = self.endpoints_file.as_deref() {
+            endpoints.extend(load_endpoints(path)?);
+        }
+
+        if endpoints.is_empty() {
+            return Err(eyre::eyre!("WS or HTTP RPC URL not provided"));
+        }
+        for (i, endpoint) in endpoints.iter().enumerate() {
+            if endpoints[..i].iter().any(|e| e.name == endpoint.name) {
+                return Err(eyre::eyre!("duplicate endpoint name: {}", endpoint.name));
+            }
+        }
+
+        Ok(endpoints)
+    }
+
+    /// The block range to replay, `None` when running live.
+    pub fn resolve_replay_range(&self) -> eyre::Result<Option<(u64, u64)>> {
+        match &self.command {
+            Some(Command::Replay(args)) => {
+                if args.from > args.to {
+                    return Err(eyre::eyre!(
+                        "replay range is empty: --from {} is after --to {}",
+                        args.from,
+                        args.to
+                    ));
                 }
-                Err(e) => Err(eyre::eyre!("failed to parse WS RPC URL: {}", e)),
-            },
-            None => Err(eyre::eyre!("WS RPC URL not provided")),
+                Ok(Some((args.from, args.to)))
+            }
+            None => Ok(None),
         }
     }
 
-    pub fn resolve_influxdb_host(&self) -> eyre::Result<String> {
-        if self.influxdb_host.is_empty() {
-            Err(eyre::eyre!("INFLUXDB_HOST not provided"))
+    pub fn resolve_replay_endpoints(&self) -> eyre::Result<Vec<Endpoint>> {
+        match &self.command {
+            Some(Command::Replay(ReplayArgs {
+                rpc_url: Some(rpc_url),
+                ..
+            })) => Ok(vec![rpc_url.parse::<Endpoint>()?]),
+            _ => self.resolve_endpoints(),
+        }
+    }
+
+    pub fn resolve_http_rpc_url(&self) -> eyre::Result<Option<String>> {
+        match self.http_rpc_url.as_deref() {
+            Some(value) if !value.is_empty() => Ok(Some(value.to_string())),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn resolve_chain(&self) -> eyre::Result<Chain> {
+        self.chain.parse()
+    }
+
+    pub fn resolve_block_fetch(&self) -> eyre::Result<BlockFetch> {
+        self.block_fetch.parse()
+    }
+
+    pub fn resolve_throughput_clock(&self) -> eyre::Result<ThroughputClock> {
+        self.throughput_clock.parse()
+    }
+
+    pub fn resolve_rolling_windows(&self) -> eyre::Result<Vec<u64>> {
+        self.rolling_windows
+            .iter()
+            .map(|w| parse_window(w))
+            .collect()
+    }
+
+    pub fn resolve_trace_config(&self) -> eyre::Result<TraceConfig> {
+        Ok(TraceConfig {
+            chain: self.resolve_chain()?,
+            block_fetch: self.resolve_block_fetch()?,
+            throughput_clock: self.resolve_throughput_clock()?,
+            rolling_windows: self.resolve_rolling_windows()?,
+            max_backfill: self.max_backfill,
+            poll_interval: self.resolve_poll_interval()?,
+            reconnect: self.resolve_reconnect_policy()?,
+            blob_base_fee_update_fraction: self.resolve_blob_base_fee_update_fraction()?,
+            pending_txs: self.pending_txs,
+        })
+    }
+
+    pub fn resolve_blob_base_fee_update_fraction(&self) -> eyre::Result<u64> {
+        if self.blob_base_fee_update_fraction == 0 {
+            return Err(eyre::eyre!(
+                "BLOB_BASE_FEE_UPDATE_FRACTION must not be zero"
+            ));
+        }
+        Ok(self.blob_base_fee_update_fraction)
+    }
+
+    pub fn resolve_health_thresholds(&self) -> eyre::Result<HealthThresholds> {
+        if !(0.0..=1.0).contains(&self.health_max_sink_error_rate) {
+            return Err(eyre::eyre!(
+                "HEALTH_MAX_SINK_ERROR_RATE must be between 0 and 1"
+            ));
+        }
+        Ok(HealthThresholds {
+            max_head_age: Duration::from_secs(parse_window(&self.health_max_head_age)?),
+            max_sink_error_rate: self.health_max_sink_error_rate,
+        })
+    }
+
+    pub fn resolve_reconnect_policy(&self) -> eyre::Result<ReconnectPolicy> {
+        if self.reconnect_max_backoff_ms == 0 {
+            return Err(eyre::eyre!("RECONNECT_MAX_BACKOFF_MS must not be zero"));
+        }
+        let max_backoff = Duration::from_millis(self.reconnect_max_backoff_ms);
+
+        match self.reconnect_policy.as_str() {
+            "unlimited" => Ok(ReconnectPolicy::Unlimited { max_backoff }),
+            "circuit-breaker" => {
+                if self.circuit_breaker_failures == 0 {
+                    return Err(eyre::eyre!("CIRCUIT_BREAKER_FAILURES must not be zero"));
+                }
+                Ok(ReconnectPolicy::CircuitBreaker {
+                    max_backoff,
+                    failures: self.circuit_breaker_failures,
+                    cooldown: Duration::from_secs(parse_window(&self.circuit_breaker_cooldown)?),
+                })
+            }
+            policy => Err(eyre::eyre!("invalid reconnect policy: {}", policy)),
+        }
+    }
+
+    pub fn resolve_poll_interval(&self) -> eyre::Result<Duration> {
+        if self.poll_interval_ms == 0 {
+            return Err(eyre::eyre!("POLL_INTERVAL_MS must not be zero"));
+        }
+        Ok(Duration::from_millis(self.poll_interval_ms))
+    }
+
+    pub fn resolve_tui(&self) -> eyre::Result<bool> {
+        Ok(self.tui)
+    }
+
+    pub fn resolve_report(&self) -> eyre::Result<bool> {
+        Ok(self.report || self.report_json.is_some())
+    }
+
+    pub fn resolve_report_json(&self) -> eyre::Result<Option<String>> {
+        Ok(self.report_json.clone())
+    }
+
+    pub fn resolve_duration(&self) -> eyre::Result<Option<Duration>> {
+        self.duration
+            .as_deref()
+            .map(|duration| Ok(Duration::from_secs(parse_window(duration)?)))
+            .transpose()
+    }
+
+    /// Without `--sink` blockspeed writes to InfluxDB, unless the TUI or the report is all
+    /// that's wanted.
+    pub fn resolve_sinks(&self) -> eyre::Result<Vec<SinkKind>> {
+        if self.sinks.is_empty() {
+            return Ok(if self.tui || self.resolve_report()? {
+                Vec::new()
+            } else {
+                vec![SinkKind::InfluxDb]
+            });
+        }
+
+        let mut sinks = Vec::new();
+        for sink in &self.sinks {
+            let kind = sink.parse::<SinkKind>()?;
+            if !sinks.contains(&kind) {
+                sinks.push(kind);
+            }
+        }
+        Ok(sinks)
+    }
+
+    pub fn resolve_schema(&self) -> eyre::Result<Schema> {
+        if self.legacy_schema {
+            Ok(Schema::Legacy)
         } else {
-            Ok(self.influxdb_host.clone())
+            Ok(Schema::Block)
+        }
+    }
+
+    pub fn resolve_buffer_config(&self) -> eyre::Result<BufferConfig> {
+        if self.sink_buffer_capacity == 0 {
+            return Err(eyre::eyre!("SINK_BUFFER_CAPACITY must not be zero"));
+        }
+        if self.sink_batch_size == 0 {
+            return Err(eyre::eyre!("SINK_BATCH_SIZE must not be zero"));
+        }
+        if self.sink_flush_interval_ms == 0 {
+            return Err(eyre::eyre!("SINK_FLUSH_INTERVAL_MS must not be zero"));
+        }
+
+        Ok(BufferConfig {
+            capacity: self.sink_buffer_capacity,
+            batch_size: self.sink_batch_size,
+            flush_interval: Duration::from_millis(self.sink_flush_interval_ms),
+            // a replay can wait for a slow sink, a live subscription can't
+            wait_when_full: matches!(self.command, Some(Command::Replay(_))),
+        })
+    }
+
+    pub fn resolve_file_path(&self) -> eyre::Result<String> {
+        match self.file_path.as_deref() {
+            Some(value) if !value.is_empty() => Ok(value.to_string()),
+            _ => Err(eyre::eyre!("FILE_PATH not provided")),
+        }
+    }
+
+    pub fn resolve_file_format(&self) -> eyre::Result<FileFormat> {
+        self.file_format.parse()
+    }
+
+    pub fn resolve_file_rotation(&self) -> eyre::Result<Rotation> {
+        let max_age = match self.file_rotate_interval.as_deref() {
+            Some(interval) if !interval.is_empty() => {
+                Some(Duration::from_secs(parse_window(interval)?))
+            }
+            _ => None,
+        };
+        Ok(Rotation {
+            max_bytes: (self.file_max_bytes > 0).then_some(self.file_max_bytes),
+            max_age,
+        })
+    }
+
+    pub fn resolve_spool(&self) -> eyre::Result<Option<Spool>> {
+        match self.spool_path.as_deref() {
+            Some(path) if !path.is_empty() => Ok(Some(Spool::open(path, self.spool_max_bytes)?)),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn resolve_line_protocol_url(&self) -> eyre::Result<String> {
+        match self.line_protocol_url.as_deref() {
+            Some(value) if !value.is_empty() => Ok(value.to_string()),
+            _ => Err(eyre::eyre!("LINE_PROTOCOL_URL not provided")),
+        }
+    }
+
+    pub fn resolve_line_protocol_options(&self) -> eyre::Result<V1Options> {
+        let non_empty = |value: &Option<String>| value.clone().filter(|v| !v.is_empty());
+        Ok(V1Options {
+            db: non_empty(&self.line_protocol_db),
+            rp: non_empty(&self.line_protocol_rp),
+            username: non_empty(&self.line_protocol_user),
+            password: non_empty(&self.line_protocol_password),
+        })
+    }
+
+    pub fn resolve_influxdb_host(&self) -> eyre::Result<String> {
+        match self.influxdb_host.as_deref() {
+            Some(value) if !value.is_empty() => Ok(value.to_string()),
+            _ => Err(eyre::eyre!("INFLUXDB_HOST not provided")),
         }
     }
     pub fn resolve_influxdb_org(&self) -> eyre::Result<String> {
-        if self.influxdb_org.is_empty() {
-            Err(eyre::eyre!("INFLUXDB_ORG not provided"))
-        } else {
-            Ok(self.influxdb_org.clone())
+        match self.influxdb_org.as_deref() {
+            Some(value) if !value.is_empty() => Ok(value.to_string()),
+            _ => Err(eyre::eyre!("INFLUXDB_ORG not provided")),
         }
     }
 
     pub fn resolve_influxdb_token(&self) -> eyre::Result<String> {
-        if self.influxdb_token.is_empty() {
-            Err(eyre::eyre!("INFLUXDB_TOKEN not provided"))
-        } else {
-            Ok(self.influxdb_token.clone())
+        match self.influxdb_token.as_deref() {
+            Some(value) if !value.is_empty() => Ok(value.to_string()),
+            _ => Err(eyre::eyre!("INFLUXDB_TOKEN not provided")),
         }
     }
 
     pub fn resolve_influxdb_bucket(&self) -> eyre::Result<String> {
-        if self.influxdb_bucket.is_empty() {
-            Err(eyre::eyre!("INFLUXDB_BUCKET not provided"))
-        } else {
-            Ok(self.influxdb_bucket.clone())
+        match self.influxdb_bucket.as_deref() {
+            Some(value) if !value.is_empty() => Ok(value.to_string()),
+            _ => Err(eyre::eyre!("INFLUXDB_BUCKET not provided")),
         }
     }
 }
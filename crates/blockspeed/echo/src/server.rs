@@ -1,5 +1,6 @@
 #![allow(missing_docs)]
 
+use blockspeed_trace::status::{HealthThresholds, StatusRegistry};
 use bytes::Bytes;
 use http_body_util::{combinators::BoxBody, BodyExt, Empty, Full};
 use hyper::server::conn::http1;
@@ -7,16 +8,36 @@ use hyper::service::service_fn;
 use hyper::{Method, Request, Response, StatusCode};
 use hyper_util::rt::TokioIo;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::TcpListener;
 use tokio::signal::unix::{signal, SignalKind};
-use tracing::info;
+use tracing::{info, warn};
 
 async fn echo(
     req: Request<hyper::body::Incoming>,
+    status: Arc<StatusRegistry>,
+    thresholds: Arc<HealthThresholds>,
 ) -> eyre::Result<Response<BoxBody<Bytes, hyper::Error>>> {
     match (req.method(), req.uri().path()) {
-        (&Method::GET, "/health") => Ok(Response::new(full("ok"))),
+        // the process stays up while endpoints reconnect, readiness fails once an endpoint or
+        // sink is past its threshold
+        (&Method::GET, "/health") => {
+            let report = status.report(&thresholds);
+            if !report.healthy {
+                warn!(problems = ?report.problems, "blockspeed is unhealthy");
+            }
+
+            let mut response = Response::new(full(serde_json::to_vec(&report)?));
+            response.headers_mut().insert(
+                hyper::header::CONTENT_TYPE,
+                hyper::header::HeaderValue::from_static("application/json"),
+            );
+            if !report.healthy {
+                *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            }
+            Ok(response)
+        }
 
         // return 404 Not Found for other routes.
         _ => {
@@ -39,7 +60,13 @@ fn full<T: Into<Bytes>>(chunk: T) -> BoxBody<Bytes, hyper::Error> {
         .boxed()
 }
 
-pub async fn serve_echo(addr: SocketAddr) -> eyre::Result<()> {
+pub async fn serve_echo(
+    addr: SocketAddr,
+    status: Arc<StatusRegistry>,
+    thresholds: HealthThresholds,
+) -> eyre::Result<()> {
+    let thresholds = Arc::new(thresholds);
+
     let listener = TcpListener::bind(addr).await.unwrap();
     info!("echo listening on http://{}", addr);
 
@@ -54,7 +81,9 @@ pub async fn serve_echo(addr: SocketAddr) -> eyre::Result<()> {
                 let (tcp, _) = incoming?;
                 let io = TokioIo::new(tcp);
 
-                let service = service_fn(move |req| echo(req));
+                let status = Arc::clone(&status);
+                let thresholds = Arc::clone(&thresholds);
+                let service = service_fn(move |req| echo(req, Arc::clone(&status), Arc::clone(&thresholds)));
 
                 let connection_timeouts_clone = connection_timeouts.clone();
 
@@ -0,0 +1,293 @@
+#![allow(missing_docs)]
+
+use alloy_primitives::{Address, Bytes, B256, U128, U64};
+use serde::Deserialize;
+use std::str::FromStr;
+
+const GWEI: f64 = 1_000_000_000.0;
+
+/// How much of each block is fetched on top of the `newHeads` header.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BlockFetch {
+    /// only the transaction count (`eth_getBlockTransactionCountByHash`)
+    #[default]
+    Header,
+    /// the full block with transaction objects (`eth_getBlockByHash`)
+    Full,
+    /// all receipts of the block (`eth_getBlockReceipts`)
+    Receipts,
+}
+
+impl FromStr for BlockFetch {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "header" => Ok(Self::Header),
+            "full" => Ok(Self::Full),
+            "receipts" => Ok(Self::Receipts),
+            _ => Err(eyre::eyre!("invalid block fetch mode: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FullBlock {
+    pub transactions: Vec<BlockTransaction>,
+}
+
+/// The subset of transaction fields needed for block stats, inclusion latency and the L1
+/// origin of OP Stack blocks. Kept loose on purpose so that non-standard transaction types
+/// (e.g. L2 deposits) still deserialize.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockTransaction {
+    #[serde(default)]
+    pub hash: B256,
+    #[serde(rename = "type", default)]
+    pub tx_type: U64,
+    pub to: Option<Address>,
+    pub gas_price: Option<U128>,
+    pub max_fee_per_gas: Option<U128>,
+    pub max_priority_fee_per_gas: Option<U128>,
+    #[serde(default)]
+    pub input: Bytes,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockReceipt {
+    #[serde(default)]
+    pub transaction_hash: B256,
+    #[serde(rename = "type", default)]
+    pub tx_type: U64,
+    pub contract_address: Option<Address>,
+    pub effective_gas_price: Option<U128>,
+}
+
+/// Gas and fee market values taken from the header, available whatever [`BlockFetch`] is.
+/// Fields are `None` before the fork that introduced them.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FeeMarket {
+    pub gas_limit: u64,
+    /// gas used over gas limit
+    pub gas_utilisation: f64,
+    pub base_fee_gwei: Option<f64>,
+    pub blob_gas_used: Option<u64>,
+    pub excess_blob_gas: Option<u64>,
+    pub blob_base_fee_gwei: Option<f64>,
+}
+
+impl FeeMarket {
+    pub fn new(
+        gas_used: u64,
+        gas_limit: u64,
+        base_fee: Option<u128>,
+        blob_gas_used: Option<u64>,
+        excess_blob_gas: Option<u64>,
+        blob_base_fee_update_fraction: u64,
+    ) -> Self {
+        let gas_utilisation = if gas_limit > 0 {
+            gas_used as f64 / gas_limit as f64
+        } else {
+            0.0
+        };
+
+        Self {
+            gas_limit,
+            gas_utilisation,
+            base_fee_gwei: base_fee.map(|fee| fee as f64 / GWEI),
+            blob_gas_used,
+            excess_blob_gas,
+            blob_base_fee_gwei: excess_blob_gas
+                .map(|excess| blob_base_fee(excess, blob_base_fee_update_fraction) as f64 / GWEI),
+        }
+    }
+}
+
+/// Blob base fee in wei for a block's `excessBlobGas` (EIP-4844). The update fraction depends
+/// on the fork: 3338477 for Cancun, 5007716 for Prague.
+pub fn blob_base_fee(excess_blob_gas: u64, update_fraction: u64) -> u128 {
+    const MIN_BLOB_BASE_FEE: u128 = 1;
+    fake_exponential(
+        MIN_BLOB_BASE_FEE,
+        excess_blob_gas as u128,
+        update_fraction.max(1) as u128,
+    )
+}
+
+/// Integer approximation of `factor * e ** (numerator / denominator)` from EIP-4844.
+fn fake_exponential(factor: u128, numerator: u128, denominator: u128) -> u128 {
+    let mut i = 1;
+    let mut output = 0u128;
+    let mut accum = factor.saturating_mul(denominator);
+    while accum > 0 {
+        output = output.saturating_add(accum);
+        accum = accum.saturating_mul(numerator) / (denominator * i);
+        i += 1;
+    }
+    output / denominator
+}
+
+/// Longest builder tag kept from `extraData`, which is at most 32 bytes on mainnet but not
+/// bounded on every chain.
+const MAX_BUILDER_TAG: usize = 32;
+
+/// Decodes a block's `extraData` into a builder tag such as `beaverbuild.org` or
+/// `Titan (titanbuilder.xyz)`. `None` unless every byte is printable ASCII: random bytes and
+/// RLP encoded client versions would otherwise give a new tag, and a new series, per block.
+pub fn builder_tag(extra_data: &[u8]) -> Option<String> {
+    if !extra_data.iter().all(|b| (0x20..=0x7e).contains(b)) {
+        return None;
+    }
+    let tag: String = String::from_utf8_lossy(extra_data)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(MAX_BUILDER_TAG)
+        .collect();
+    (!tag.is_empty()).then_some(tag)
+}
+
+/// Per-block statistics derived from the block transactions or receipts, depending on
+/// [`BlockFetch`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BlockStats {
+    pub priority_fee_p50_gwei: f64,
+    pub priority_fee_p90_gwei: f64,
+    pub priority_fee_p99_gwei: f64,
+    pub legacy_txs: u64,
+    pub access_list_txs: u64,
+    pub eip1559_txs: u64,
+    pub blob_txs: u64,
+    pub set_code_txs: u64,
+    pub other_txs: u64,
+    pub contract_creations: u64,
+}
+
+impl BlockStats {
+    pub fn add_transactions(&mut self, txs: &[BlockTransaction], base_fee: u128) {
+        let mut tips = Vec::with_capacity(txs.len());
+
+        for tx in txs {
+            self.count_type(tx.tx_type.to::<u64>());
+            if tx.to.is_none() {
+                self.contract_creations += 1;
+            }
+
+            let tip = match (tx.max_fee_per_gas, tx.max_priority_fee_per_gas) {
+                (Some(max_fee), Some(max_priority_fee)) => max_priority_fee
+                    .to::<u128>()
+                    .min(max_fee.to::<u128>().saturating_sub(base_fee)),
+                _ => tx
+                    .gas_price
+                    .map(|p| p.to::<u128>())
+                    .unwrap_or_default()
+                    .saturating_sub(base_fee),
+            };
+            tips.push(tip);
+        }
+
+        self.set_priority_fees(tips);
+    }
+
+    pub fn add_receipts(&mut self, receipts: &[BlockReceipt], base_fee: u128) {
+        let mut tips = Vec::with_capacity(receipts.len());
+
+        for receipt in receipts {
+            self.count_type(receipt.tx_type.to::<u64>());
+            if receipt.contract_address.is_some() {
+                self.contract_creations += 1;
+            }
+
+            let price = receipt
+                .effective_gas_price
+                .map(|p| p.to::<u128>())
+                .unwrap_or_default();
+            tips.push(price.saturating_sub(base_fee));
+        }
+
+        self.set_priority_fees(tips);
+    }
+
+    fn count_type(&mut self, tx_type: u64) {
+        match tx_type {
+            0 => self.legacy_txs += 1,
+            1 => self.access_list_txs += 1,
+            2 => self.eip1559_txs += 1,
+            3 => self.blob_txs += 1,
+            4 => self.set_code_txs += 1,
+            _ => self.other_txs += 1,
+        }
+    }
+
+    fn set_priority_fees(&mut self, mut tips: Vec<u128>) {
+        tips.sort_unstable();
+        self.priority_fee_p50_gwei = percentile(&tips, 50.0) as f64 / GWEI;
+        self.priority_fee_p90_gwei = percentile(&tips, 90.0) as f64 / GWEI;
+        self.priority_fee_p99_gwei = percentile(&tips, 99.0) as f64 / GWEI;
+    }
+}
+
+/// Nearest-rank percentile of an already sorted slice, `0` when empty.
+pub fn percentile<T: Copy + Default>(sorted: &[T], pct: f64) -> T {
+    if sorted.is_empty() {
+        return T::default();
+    }
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CANCUN_UPDATE_FRACTION: u64 = 3_338_477;
+    const PRAGUE_UPDATE_FRACTION: u64 = 5_007_716;
+
+    #[test]
+    fn fake_exponential_matches_the_reference_vectors() {
+        // from the EIP-4844 reference implementation tests
+        for (factor, numerator, denominator, expected) in [
+            (1, 0, 1, 1),
+            (38493, 0, 1000, 38493),
+            (0, 1234, 2345, 0),
+            (1, 2, 1, 6),
+            (1, 3, 1, 16),
+            (10, 8, 2, 542),
+            (2, 5, 2, 23),
+            (1, 50_000_000, 2_225_652, 5_709_098_764),
+        ] {
+            assert_eq!(
+                fake_exponential(factor, numerator, denominator),
+                expected,
+                "fake_exponential({}, {}, {})",
+                factor,
+                numerator,
+                denominator
+            );
+        }
+    }
+
+    #[test]
+    fn blob_base_fee_at_zero_excess_is_the_minimum() {
+        // e.g. mainnet block 19426587, the first Cancun block
+        assert_eq!(blob_base_fee(0, CANCUN_UPDATE_FRACTION), 1);
+        assert_eq!(blob_base_fee(0, PRAGUE_UPDATE_FRACTION), 1);
+    }
+
+    #[test]
+    fn blob_base_fee_depends_on_the_fork() {
+        // 100 blocks worth of blob gas above target
+        let excess = 100 * 393_216;
+        assert_eq!(blob_base_fee(excess, CANCUN_UPDATE_FRACTION), 130_392);
+        assert_eq!(blob_base_fee(excess, PRAGUE_UPDATE_FRACTION), 2_571);
+    }
+
+    #[test]
+    fn blob_base_fee_tolerates_a_zero_update_fraction() {
+        assert_eq!(blob_base_fee(0, 0), 1);
+    }
+}
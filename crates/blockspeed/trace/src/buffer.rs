@@ -0,0 +1,267 @@
+#![allow(missing_docs)]
+
+use crate::chain::Reorg;
+use crate::sink::{BlockMetrics, MetricsSink};
+use crate::status::StatusRegistry;
+use futures_util::future::BoxFuture;
+use metrics::{counter, describe_counter, describe_gauge, gauge};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, error, warn};
+
+#[derive(Debug, Clone)]
+pub struct BufferConfig {
+    /// blocks (and reorgs) held in memory before new ones are dropped
+    pub capacity: usize,
+    /// flush as soon as this many blocks are pending
+    pub batch_size: usize,
+    /// flush whatever is pending at least this often
+    pub flush_interval: Duration,
+    /// wait for room instead of dropping when the queue is full, for replays where nothing
+    /// is lost by slowing down
+    pub wait_when_full: bool,
+}
+
+impl Default for BufferConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 10_000,
+            batch_size: 100,
+            flush_interval: Duration::from_secs(1),
+            wait_when_full: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Entry {
+    Block(Box<BlockMetrics>),
+    Reorg {
+        endpoint: String,
+        reorg: Reorg,
+        timestamp_nanos: u64,
+    },
+    /// write everything queued before it, then acknowledge
+    Flush(oneshot::Sender<()>),
+}
+
+/// Decouples header processing from a slow sink. Writes are queued without waiting, a
+/// background task drains the queue and hands the inner sink one batch of blocks per flush.
+/// When the queue is full new entries are dropped and counted rather than blocking.
+#[derive(Debug)]
+pub struct BufferedSink {
+    name: String,
+    tx: mpsc::Sender<Entry>,
+    wait_when_full: bool,
+}
+
+impl BufferedSink {
+    /// Spawns the background writer, must be called from within a tokio runtime. The outcome
+    /// of every write is recorded in `status` for the health endpoint.
+    pub fn new(
+        name: &str,
+        inner: Arc<dyn MetricsSink>,
+        config: BufferConfig,
+        status: Arc<StatusRegistry>,
+    ) -> Self {
+        describe_gauge!(
+            "blockspeed_sink_queue_depth",
+            "Entries waiting in the sink buffer"
+        );
+        describe_counter!(
+            "blockspeed_sink_dropped",
+            "Entries dropped because the sink buffer was full"
+        );
+        describe_counter!(
+            "blockspeed_sink_flushes",
+            "Batches written from the sink buffer"
+        );
+        describe_counter!(
+            "blockspeed_sink_flush_errors",
+            "Batches the inner sink failed to write"
+        );
+
+        let (tx, rx) = mpsc::channel(config.capacity.max(1));
+        let wait_when_full = config.wait_when_full;
+        tokio::spawn(run_writer(name.to_string(), inner, config, status, rx));
+
+        Self {
+            name: name.to_string(),
+            tx,
+            wait_when_full,
+        }
+    }
+
+    fn enqueue(&self, entry: Entry) {
+        match self.tx.try_send(entry) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                counter!("blockspeed_sink_dropped", "sink" => self.name.clone()).increment(1);
+                warn!(sink = %self.name, "sink buffer full, dropping entry");
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                counter!("blockspeed_sink_dropped", "sink" => self.name.clone()).increment(1);
+                error!(sink = %self.name, "sink writer stopped, dropping entry");
+            }
+        }
+        gauge!("blockspeed_sink_queue_depth", "sink" => self.name.clone())
+            .set(queue_depth(&self.tx) as f64);
+    }
+}
+
+impl MetricsSink for BufferedSink {
+    fn write_blocks<'a>(&'a self, blocks: &'a [BlockMetrics]) -> BoxFuture<'a, eyre::Result<()>> {
+        if self.wait_when_full {
+            return Box::pin(async move {
+                for block in blocks {
+                    self.tx
+                        .send(Entry::Block(Box::new(block.clone())))
+                        .await
+                        .map_err(|_| eyre::eyre!("sink writer for {} stopped", self.name))?;
+                }
+                gauge!("blockspeed_sink_queue_depth", "sink" => self.name.clone())
+                    .set(queue_depth(&self.tx) as f64);
+                Ok(())
+            });
+        }
+
+        for block in blocks {
+            self.enqueue(Entry::Block(Box::new(block.clone())));
+        }
+        Box::pin(async { Ok(()) })
+    }
+
+    fn write_reorg<'a>(
+        &'a self,
+        endpoint: &'a str,
+        reorg: &'a Reorg,
+        timestamp_nanos: u64,
+    ) -> BoxFuture<'a, eyre::Result<()>> {
+        let entry = Entry::Reorg {
+            endpoint: endpoint.to_string(),
+            reorg: reorg.clone(),
+            timestamp_nanos,
+        };
+        if self.wait_when_full {
+            return Box::pin(async move {
+                self.tx
+                    .send(entry)
+                    .await
+                    .map_err(|_| eyre::eyre!("sink writer for {} stopped", self.name))?;
+                gauge!("blockspeed_sink_queue_depth", "sink" => self.name.clone())
+                    .set(queue_depth(&self.tx) as f64);
+                Ok(())
+            });
+        }
+
+        self.enqueue(entry);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn flush(&self) -> BoxFuture<'_, eyre::Result<()>> {
+        Box::pin(async move {
+            let (ack_tx, ack_rx) = oneshot::channel();
+            // unlike writes a flush waits for room in the queue
+            self.tx
+                .send(Entry::Flush(ack_tx))
+                .await
+                .map_err(|_| eyre::eyre!("sink writer for {} stopped", self.name))?;
+            ack_rx
+                .await
+                .map_err(|_| eyre::eyre!("sink writer for {} stopped", self.name))?;
+            Ok(())
+        })
+    }
+}
+
+fn queue_depth(tx: &mpsc::Sender<Entry>) -> usize {
+    tx.max_capacity() - tx.capacity()
+}
+
+async fn run_writer(
+    name: String,
+    inner: Arc<dyn MetricsSink>,
+    config: BufferConfig,
+    status: Arc<StatusRegistry>,
+    mut rx: mpsc::Receiver<Entry>,
+) {
+    let batch_size = config.batch_size.max(1);
+    let mut blocks = Vec::with_capacity(batch_size);
+    let mut reorgs = Vec::new();
+    let mut interval = tokio::time::interval(config.flush_interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        let closed = tokio::select! {
+            entry = rx.recv() => match entry {
+                Some(Entry::Block(block)) => {
+                    blocks.push(*block);
+                    false
+                }
+                Some(Entry::Reorg { endpoint, reorg, timestamp_nanos }) => {
+                    reorgs.push((endpoint, reorg, timestamp_nanos));
+                    false
+                }
+                Some(Entry::Flush(ack)) => {
+                    flush(&name, inner.as_ref(), &status, &mut blocks, &mut reorgs).await;
+                    if let Err(e) = inner.flush().await {
+                        error!(sink = %name, "failed to flush: {}", e);
+                    }
+                    let _ = ack.send(());
+                    false
+                }
+                None => true,
+            },
+            _ = interval.tick() => {
+                flush(&name, inner.as_ref(), &status, &mut blocks, &mut reorgs).await;
+                false
+            }
+        };
+
+        gauge!("blockspeed_sink_queue_depth", "sink" => name.clone()).set(rx.len() as f64);
+
+        if closed {
+            flush(&name, inner.as_ref(), &status, &mut blocks, &mut reorgs).await;
+            debug!(sink = %name, "sink writer stopped");
+            return;
+        }
+        if blocks.len() >= batch_size {
+            flush(&name, inner.as_ref(), &status, &mut blocks, &mut reorgs).await;
+        }
+    }
+}
+
+async fn flush(
+    name: &str,
+    inner: &dyn MetricsSink,
+    status: &StatusRegistry,
+    blocks: &mut Vec<BlockMetrics>,
+    reorgs: &mut Vec<(String, Reorg, u64)>,
+) {
+    if blocks.is_empty() && reorgs.is_empty() {
+        return;
+    }
+
+    if !blocks.is_empty() {
+        debug!(sink = %name, blocks = blocks.len(), "flushing blocks");
+        let result = inner.write_blocks(blocks).await;
+        status.record_write(name, result.is_ok());
+        if let Err(e) = result {
+            counter!("blockspeed_sink_flush_errors", "sink" => name.to_string()).increment(1);
+            error!(sink = %name, "failed to write {} blocks: {}", blocks.len(), e);
+        }
+        blocks.clear();
+    }
+
+    for (endpoint, reorg, timestamp_nanos) in reorgs.drain(..) {
+        let result = inner.write_reorg(&endpoint, &reorg, timestamp_nanos).await;
+        status.record_write(name, result.is_ok());
+        if let Err(e) = result {
+            counter!("blockspeed_sink_flush_errors", "sink" => name.to_string()).increment(1);
+            error!(sink = %name, "failed to write reorg: {}", e);
+        }
+    }
+
+    counter!("blockspeed_sink_flushes", "sink" => name.to_string()).increment(1);
+}
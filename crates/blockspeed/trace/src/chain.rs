@@ -0,0 +1,164 @@
+#![allow(missing_docs)]
+
+use std::collections::VecDeque;
+
+/// How many recent canonical blocks are kept to find the common ancestor of a reorg.
+pub const CHAIN_WINDOW: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainBlock {
+    pub number: u64,
+    pub hash: String,
+    pub parent_hash: String,
+    pub block_timestamp: u64,
+    /// timestamp the block's points were written at, used to mark them as orphaned
+    pub timestamp_nanos: u64,
+    /// tags of the block's point, the orphan marker has to land in the same series
+    pub fee_recipient: String,
+    pub builder: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reorg {
+    pub common_ancestor: ChainBlock,
+    pub orphaned: Vec<ChainBlock>,
+    /// hashes of the new canonical blocks between the common ancestor and the new head
+    pub reemitted: Vec<String>,
+    pub new_head: u64,
+}
+
+impl Reorg {
+    pub fn depth(&self) -> usize {
+        self.orphaned.len()
+    }
+}
+
+/// A short window of the canonical chain as seen by one endpoint.
+#[derive(Debug, Default)]
+pub struct CanonicalChain {
+    blocks: VecDeque<ChainBlock>,
+}
+
+impl CanonicalChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tip(&self) -> Option<&ChainBlock> {
+        self.blocks.back()
+    }
+
+    /// Whether a new block with `parent_hash` doesn't build on the tip. An empty window has
+    /// nothing to reorg.
+    pub fn forks_from_tip(&self, parent_hash: &str) -> bool {
+        self.tip().is_some_and(|tip| tip.hash != parent_hash)
+    }
+
+    pub fn find(&self, hash: &str) -> Option<&ChainBlock> {
+        self.blocks.iter().rev().find(|b| b.hash == hash)
+    }
+
+    pub fn push(&mut self, block: ChainBlock) {
+        self.blocks.push_back(block);
+        while self.blocks.len() > CHAIN_WINDOW {
+            self.blocks.pop_front();
+        }
+    }
+
+    /// Drops and returns every block above `number`, oldest first.
+    pub fn truncate_after(&mut self, number: u64) -> Vec<ChainBlock> {
+        let keep = self
+            .blocks
+            .iter()
+            .take_while(|b| b.number <= number)
+            .count();
+        self.blocks.drain(keep..).collect()
+    }
+
+    /// Forgets the window, e.g. after a gap too large to backfill broke the parent links.
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(number: u64, hash: &str, parent_hash: &str) -> ChainBlock {
+        ChainBlock {
+            number,
+            hash: hash.to_string(),
+            parent_hash: parent_hash.to_string(),
+            block_timestamp: number * 12,
+            timestamp_nanos: number * 12_000_000_000,
+            fee_recipient: String::new(),
+            builder: None,
+        }
+    }
+
+    fn chain(blocks: &[(u64, &str, &str)]) -> CanonicalChain {
+        let mut chain = CanonicalChain::new();
+        for (number, hash, parent_hash) in blocks {
+            chain.push(block(*number, hash, parent_hash));
+        }
+        chain
+    }
+
+    #[test]
+    fn empty_window_has_nothing_to_reorg() {
+        let chain = CanonicalChain::new();
+        assert!(chain.tip().is_none());
+        assert!(!chain.forks_from_tip("0xa"));
+    }
+
+    #[test]
+    fn child_of_the_tip_extends_it() {
+        let chain = chain(&[(1, "0x1", "0x0"), (2, "0x2", "0x1")]);
+        assert!(!chain.forks_from_tip("0x2"));
+    }
+
+    #[test]
+    fn depth_one_reorg_at_the_tip() {
+        let mut chain = chain(&[(1, "0x1", "0x0"), (2, "0x2", "0x1"), (3, "0x3", "0x2")]);
+
+        // 3' replaces 3 and builds on 2
+        assert!(chain.forks_from_tip("0x2"));
+        let ancestor = chain.find("0x2").cloned().unwrap();
+        let orphaned = chain.truncate_after(ancestor.number);
+
+        assert_eq!(orphaned, vec![block(3, "0x3", "0x2")]);
+        assert_eq!(chain.tip(), Some(&ancestor));
+        chain.push(block(3, "0x3b", "0x2"));
+        assert!(!chain.forks_from_tip("0x3b"));
+    }
+
+    #[test]
+    fn deeper_reorg_orphans_oldest_first() {
+        let mut chain = chain(&[
+            (1, "0x1", "0x0"),
+            (2, "0x2", "0x1"),
+            (3, "0x3", "0x2"),
+            (4, "0x4", "0x3"),
+        ]);
+        let orphaned = chain.truncate_after(2);
+        let numbers: Vec<u64> = orphaned.iter().map(|b| b.number).collect();
+        assert_eq!(numbers, vec![3, 4]);
+        assert_eq!(chain.tip().map(|b| b.number), Some(2));
+    }
+
+    #[test]
+    fn window_keeps_the_most_recent_blocks() {
+        let mut chain = CanonicalChain::new();
+        for number in 0..CHAIN_WINDOW as u64 + 10 {
+            chain.push(block(number, &format!("0x{:x}", number), ""));
+        }
+        assert!(chain.find("0x0").is_none());
+        assert!(chain.find("0x9").is_none());
+        assert!(chain.find("0xa").is_some());
+        assert_eq!(chain.blocks.len(), CHAIN_WINDOW);
+
+        chain.clear();
+        assert!(chain.tip().is_none());
+    }
+}
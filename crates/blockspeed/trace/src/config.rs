@@ -0,0 +1,125 @@
+#![allow(missing_docs)]
+
+use crate::block::BlockFetch;
+use crate::l2::Chain;
+use crate::reconnect::ReconnectPolicy;
+use crate::throughput::ThroughputClock;
+use serde::Deserialize;
+use std::str::FromStr;
+use std::time::Duration;
+use url::Url;
+
+/// Settings shared by every endpoint blockspeed traces.
+#[derive(Debug, Clone)]
+pub struct TraceConfig {
+    pub chain: Chain,
+    pub block_fetch: BlockFetch,
+    pub throughput_clock: ThroughputClock,
+    /// rolling window lengths in seconds
+    pub rolling_windows: Vec<u64>,
+    /// most blocks fetched by number when a gap in the subscription is detected
+    pub max_backfill: u64,
+    /// how often HTTP endpoints are polled for a new block
+    pub poll_interval: Duration,
+    pub reconnect: ReconnectPolicy,
+    /// `BLOB_BASE_FEE_UPDATE_FRACTION` of the chain's current fork
+    pub blob_base_fee_update_fraction: u64,
+    /// subscribe to `newPendingTransactions` next to `newHeads` to measure inclusion latency
+    pub pending_txs: bool,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            chain: Chain::default(),
+            block_fetch: BlockFetch::default(),
+            throughput_clock: ThroughputClock::default(),
+            rolling_windows: vec![60, 300, 3600],
+            max_backfill: 64,
+            poll_interval: Duration::from_secs(1),
+            reconnect: ReconnectPolicy::default(),
+            blob_base_fee_update_fraction: 5_007_716,
+            pending_txs: false,
+        }
+    }
+}
+
+/// A named RPC endpoint. The name is what ends up in the `endpoint` tag. A WebSocket `url` is
+/// subscribed to, an HTTP one is polled; `http_url` is polled instead of a WebSocket `url`
+/// that keeps failing.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Endpoint {
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub http_url: Option<String>,
+}
+
+impl Endpoint {
+    pub fn new(name: &str, url: &str) -> eyre::Result<Self> {
+        let parsed = Url::parse(url).map_err(|e| eyre::eyre!("failed to parse RPC URL: {}", e))?;
+        if !matches!(parsed.scheme(), "ws" | "wss" | "http" | "https") {
+            return Err(eyre::eyre!("invalid RPC URL scheme: {}", parsed));
+        }
+        if name.is_empty() {
+            return Err(eyre::eyre!("endpoint name must not be empty: {}", url));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            http_url: None,
+        })
+    }
+
+    /// Sets the HTTP endpoint polled when the WebSocket subscription fails repeatedly.
+    pub fn with_http_url(mut self, http_url: &str) -> eyre::Result<Self> {
+        let parsed =
+            Url::parse(http_url).map_err(|e| eyre::eyre!("failed to parse HTTP RPC URL: {}", e))?;
+        if parsed.scheme() != "http" && parsed.scheme() != "https" {
+            return Err(eyre::eyre!("invalid HTTP RPC URL scheme: {}", parsed));
+        }
+        self.http_url = Some(http_url.to_string());
+        Ok(self)
+    }
+
+    pub fn is_http(&self) -> bool {
+        self.url.starts_with("http://") || self.url.starts_with("https://")
+    }
+}
+
+/// Parses `name=url`, or a bare `url` which is then named after its host.
+impl FromStr for Endpoint {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((name, url)) if !name.contains("://") => Self::new(name.trim(), url.trim()),
+            _ => {
+                let url = Url::parse(s.trim())
+                    .map_err(|e| eyre::eyre!("failed to parse RPC URL: {}", e))?;
+                let name = url.host_str().unwrap_or_default().to_string();
+                Self::new(&name, s.trim())
+            }
+        }
+    }
+}
+
+/// Reads endpoints from a JSON file holding `[{"name": "...", "url": "..."}]`.
+pub fn load_endpoints(path: &str) -> eyre::Result<Vec<Endpoint>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| eyre::eyre!("failed to read endpoints file {}: {}", path, e))?;
+    let endpoints: Vec<Endpoint> = serde_json::from_str(&content)
+        .map_err(|e| eyre::eyre!("failed to parse endpoints file {}: {}", path, e))?;
+
+    endpoints
+        .iter()
+        .map(|endpoint| {
+            let validated = Endpoint::new(&endpoint.name, &endpoint.url)?;
+            match endpoint.http_url.as_deref() {
+                Some(http_url) => validated.with_http_url(http_url),
+                None => Ok(validated),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,296 @@
+#![allow(missing_docs)]
+
+use crate::sink::{BlockMetrics, MetricsSink};
+use futures_util::future::BoxFuture;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FileFormat {
+    #[default]
+    Csv,
+    JsonLines,
+}
+
+impl FileFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::JsonLines => "jsonl",
+        }
+    }
+}
+
+impl FromStr for FileFormat {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "jsonl" => Ok(Self::JsonLines),
+            _ => Err(eyre::eyre!("invalid file format: {}", s)),
+        }
+    }
+}
+
+/// When to start a new file, `None` disables that limit.
+#[derive(Debug, Clone, Default)]
+pub struct Rotation {
+    pub max_bytes: Option<u64>,
+    pub max_age: Option<Duration>,
+}
+
+/// One row per block. Rates are empty (CSV) or `null` (JSON) when there was no previous block
+/// to measure against, fee fields when the block predates their fork.
+#[derive(Debug, Serialize)]
+struct BlockRecord<'a> {
+    endpoint: &'a str,
+    number: u64,
+    hash: &'a str,
+    block_timestamp: u64,
+    timestamp_nanos: u64,
+    backfilled: bool,
+    gas_used: u64,
+    tx_count: u64,
+    system_tx_count: u64,
+    l1_block_number: Option<u64>,
+    fee_recipient: &'a str,
+    builder: Option<&'a str>,
+    gas_limit: u64,
+    gas_utilisation: f64,
+    base_fee_gwei: Option<f64>,
+    blob_gas_used: Option<u64>,
+    blob_base_fee_gwei: Option<f64>,
+    mgas: Option<f64>,
+    tps: Option<f64>,
+    block_time: Option<f64>,
+    mempool_inflow_tps: Option<f64>,
+    inclusion_p50_ms: Option<f64>,
+    inclusion_p95_ms: Option<f64>,
+}
+
+impl<'a> From<&'a BlockMetrics> for BlockRecord<'a> {
+    fn from(block: &'a BlockMetrics) -> Self {
+        Self {
+            endpoint: &block.endpoint,
+            number: block.number,
+            hash: &block.hash,
+            block_timestamp: block.block_timestamp,
+            timestamp_nanos: block.timestamp_nanos,
+            backfilled: block.backfilled,
+            gas_used: block.gas_used,
+            tx_count: block.tx_count,
+            system_tx_count: block.system_tx_count,
+            l1_block_number: block.l1_origin.as_ref().map(|origin| origin.number),
+            fee_recipient: &block.fee_recipient,
+            builder: block.builder.as_deref(),
+            gas_limit: block.fee_market.gas_limit,
+            gas_utilisation: block.fee_market.gas_utilisation,
+            base_fee_gwei: block.fee_market.base_fee_gwei,
+            blob_gas_used: block.fee_market.blob_gas_used,
+            blob_base_fee_gwei: block.fee_market.blob_base_fee_gwei,
+            mgas: block.rates.map(|r| r.mgas),
+            tps: block.rates.map(|r| r.tps),
+            block_time: block.rates.map(|r| r.block_time),
+            mempool_inflow_tps: block.mempool.as_ref().map(|m| m.inflow_tps),
+            inclusion_p50_ms: block
+                .mempool
+                .as_ref()
+                .and_then(|m| m.inclusion_percentile(50.0)),
+            inclusion_p95_ms: block
+                .mempool
+                .as_ref()
+                .and_then(|m| m.inclusion_percentile(95.0)),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CurrentFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    bytes: u64,
+    opened: Instant,
+}
+
+/// Writes per-block records to `<dir>/<prefix>-<unix seconds>.<csv|jsonl>`, starting a new
+/// file whenever the [`Rotation`] limits are hit. Reorgs aren't recorded, the rows carry the
+/// block hash so orphaned blocks can be told apart afterwards.
+#[derive(Debug)]
+pub struct FileSink {
+    /// file I/O runs on the blocking pool, a slow disk must not stall the runtime
+    files: Arc<Files>,
+}
+
+#[derive(Debug)]
+struct Files {
+    dir: PathBuf,
+    prefix: String,
+    format: FileFormat,
+    rotation: Rotation,
+    current: Mutex<Option<CurrentFile>>,
+}
+
+impl FileSink {
+    /// `path` is the directory and file name prefix, e.g. `out/blocks` writes
+    /// `out/blocks-1700000000.csv`.
+    pub fn new(
+        path: impl AsRef<Path>,
+        format: FileFormat,
+        rotation: Rotation,
+    ) -> eyre::Result<Self> {
+        let path = path.as_ref();
+        let prefix = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| eyre::eyre!("invalid file sink path: {}", path.display()))?
+            .to_string();
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        fs::create_dir_all(&dir)
+            .map_err(|e| eyre::eyre!("failed to create {}: {}", dir.display(), e))?;
+
+        Ok(Self {
+            files: Arc::new(Files {
+                dir,
+                prefix,
+                format,
+                rotation,
+                current: Mutex::new(None),
+            }),
+        })
+    }
+}
+
+impl Files {
+    fn open_file(&self) -> eyre::Result<CurrentFile> {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut path = self.dir.join(format!(
+            "{}-{}.{}",
+            self.prefix,
+            secs,
+            self.format.extension()
+        ));
+        // rotating twice within a second must not truncate the previous file
+        let mut n = 1;
+        while path.exists() {
+            path = self.dir.join(format!(
+                "{}-{}-{}.{}",
+                self.prefix,
+                secs,
+                n,
+                self.format.extension()
+            ));
+            n += 1;
+        }
+
+        let file = File::create(&path)
+            .map_err(|e| eyre::eyre!("failed to create {}: {}", path.display(), e))?;
+        info!("writing blocks to {}", path.display());
+
+        let mut current = CurrentFile {
+            path,
+            writer: BufWriter::new(file),
+            bytes: 0,
+            opened: Instant::now(),
+        };
+        if self.format == FileFormat::Csv {
+            let header = self.encode_csv(None)?;
+            current.writer.write_all(&header)?;
+            current.bytes += header.len() as u64;
+        }
+        Ok(current)
+    }
+
+    fn needs_rotation(&self, current: &CurrentFile) -> bool {
+        self.rotation
+            .max_bytes
+            .is_some_and(|max| current.bytes >= max)
+            || self
+                .rotation
+                .max_age
+                .is_some_and(|max| current.opened.elapsed() >= max)
+    }
+
+    /// The CSV header row when `block` is `None`, otherwise the record row.
+    fn encode_csv(&self, block: Option<&BlockMetrics>) -> eyre::Result<Vec<u8>> {
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(block.is_none())
+            .from_writer(Vec::new());
+        match block {
+            Some(block) => writer.serialize(BlockRecord::from(block))?,
+            None => writer.serialize(BlockRecord::from(&BlockMetrics::default()))?,
+        }
+        let mut bytes = writer.into_inner().map_err(|e| eyre::eyre!("{}", e))?;
+        if block.is_none() {
+            // keep only the header line
+            let end = bytes.iter().position(|b| *b == b'\n').map_or(0, |i| i + 1);
+            bytes.truncate(end);
+        }
+        Ok(bytes)
+    }
+
+    fn encode(&self, block: &BlockMetrics) -> eyre::Result<Vec<u8>> {
+        match self.format {
+            FileFormat::Csv => self.encode_csv(Some(block)),
+            FileFormat::JsonLines => {
+                let mut line = serde_json::to_vec(&BlockRecord::from(block))?;
+                line.push(b'\n');
+                Ok(line)
+            }
+        }
+    }
+
+    fn write(&self, lines: &[Vec<u8>]) -> eyre::Result<()> {
+        let mut current = self.current.lock().unwrap();
+
+        for line in lines {
+            if current
+                .as_ref()
+                .is_none_or(|file| self.needs_rotation(file))
+            {
+                if let Some(mut file) = current.take() {
+                    file.writer.flush()?;
+                    info!("rotated {}", file.path.display());
+                }
+                *current = Some(self.open_file()?);
+            }
+
+            if let Some(file) = current.as_mut() {
+                file.writer.write_all(line)?;
+                file.bytes += line.len() as u64;
+            }
+        }
+
+        if let Some(file) = current.as_mut() {
+            file.writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl MetricsSink for FileSink {
+    fn write_blocks<'a>(&'a self, blocks: &'a [BlockMetrics]) -> BoxFuture<'a, eyre::Result<()>> {
+        Box::pin(async move {
+            let lines = blocks
+                .iter()
+                .map(|block| self.files.encode(block))
+                .collect::<eyre::Result<Vec<_>>>()?;
+            let files = Arc::clone(&self.files);
+            tokio::task::spawn_blocking(move || files.write(&lines))
+                .await
+                .map_err(|e| eyre::eyre!("file write task failed: {}", e))?
+        })
+    }
+}
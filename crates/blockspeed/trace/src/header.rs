@@ -0,0 +1,87 @@
+#![allow(missing_docs)]
+
+use alloy_primitives::{B256, U64};
+use alloy_rpc_types_eth::Header;
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt;
+
+/// A header as returned by `newHeads` or `eth_getBlockBy*` without transaction objects, plus
+/// the fields L2 nodes add to it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcHeader {
+    #[serde(flatten)]
+    pub header: Header,
+    /// transaction hashes, only present on blocks fetched by number or hash
+    #[serde(default)]
+    pub transactions: Option<Vec<B256>>,
+    #[serde(flatten)]
+    pub l2: L2Fields,
+}
+
+/// Header extensions of L2 nodes. Arbitrum adds its L1 block number and outbox state. OP Stack
+/// headers have no extra fields, their L1 origin is in the L1 attributes deposit transaction.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct L2Fields {
+    /// L1 block the Arbitrum sequencer last saw
+    #[serde(default)]
+    pub l1_block_number: Option<U64>,
+    /// number of L2-to-L1 messages sent so far (Arbitrum)
+    #[serde(default)]
+    pub send_count: Option<U64>,
+    /// root of the L2-to-L1 message accumulator (Arbitrum)
+    #[serde(default)]
+    pub send_root: Option<B256>,
+}
+
+impl RpcHeader {
+    pub fn decode(value: Value) -> Result<Self, MalformedHeader> {
+        // keep what identifies the block for the error before the value is consumed
+        let hash = value["hash"].as_str().map(str::to_string);
+        let number = value["number"].as_str().map(str::to_string);
+        serde_json::from_value(value).map_err(|source| MalformedHeader {
+            hash,
+            number,
+            source,
+        })
+    }
+
+    pub fn hash(&self) -> String {
+        self.header.hash.to_string()
+    }
+
+    pub fn parent_hash(&self) -> String {
+        self.header.parent_hash.to_string()
+    }
+
+    pub fn number(&self) -> u64 {
+        self.header.number
+    }
+}
+
+/// A header that is missing a field or has one with the wrong type.
+#[derive(Debug)]
+pub struct MalformedHeader {
+    pub hash: Option<String>,
+    pub number: Option<String>,
+    pub source: serde_json::Error,
+}
+
+impl fmt::Display for MalformedHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let block = self
+            .hash
+            .as_deref()
+            .or(self.number.as_deref())
+            .unwrap_or("without hash or number");
+        write!(f, "malformed header {}: {}", block, self.source)
+    }
+}
+
+impl std::error::Error for MalformedHeader {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
@@ -0,0 +1,236 @@
+#![allow(missing_docs)]
+
+use crate::header::L2Fields;
+use std::str::FromStr;
+
+/// OP Stack deposit transactions, including the L1 attributes transaction opening every block.
+const OP_DEPOSIT_TX: u64 = 0x7e;
+/// Arbitrum ETH deposits from L1.
+const ARBITRUM_DEPOSIT_TX: u64 = 0x64;
+/// Arbitrum internal transactions, e.g. the `startBlock` call opening every block.
+const ARBITRUM_INTERNAL_TX: u64 = 0x6a;
+
+/// `setL1BlockValues` (Bedrock), ABI encoded.
+const SET_L1_BLOCK_VALUES: [u8; 4] = [0x01, 0x5d, 0x8e, 0xb9];
+/// `setL1BlockValuesEcotone`, tightly packed, later forks append to the same layout.
+const SET_L1_BLOCK_VALUES_ECOTONE: [u8; 4] = [0x44, 0x0a, 0x5e, 0x20];
+/// `setL1BlockValuesIsthmus`
+const SET_L1_BLOCK_VALUES_ISTHMUS: [u8; 4] = [0x09, 0x89, 0x99, 0xbe];
+
+/// The kind of chain an endpoint serves. L2s open blocks with system transactions that aren't
+/// user throughput and produce blocks faster than their one second timestamp resolution.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Chain {
+    #[default]
+    L1,
+    OpStack,
+    Arbitrum,
+}
+
+impl Chain {
+    pub fn is_l2(&self) -> bool {
+        !matches!(self, Self::L1)
+    }
+
+    /// Transactions that are excluded from `tx_count` and TPS.
+    pub fn is_system_tx(&self, tx_type: u64) -> bool {
+        match self {
+            Self::L1 => false,
+            Self::OpStack => tx_type == OP_DEPOSIT_TX,
+            Self::Arbitrum => matches!(tx_type, ARBITRUM_DEPOSIT_TX | ARBITRUM_INTERNAL_TX),
+        }
+    }
+}
+
+impl FromStr for Chain {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "l1" => Ok(Self::L1),
+            "op-stack" => Ok(Self::OpStack),
+            "arbitrum" => Ok(Self::Arbitrum),
+            _ => Err(eyre::eyre!("invalid chain: {}", s)),
+        }
+    }
+}
+
+/// The L1 block an L2 block was derived from, or the latest one the sequencer had seen.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct L1Origin {
+    pub number: u64,
+    /// OP Stack only
+    pub hash: Option<String>,
+    /// position of the block within its L1 epoch (OP Stack)
+    pub sequence_number: Option<u64>,
+    /// L2-to-L1 messages sent so far (Arbitrum)
+    pub send_count: Option<u64>,
+}
+
+impl L1Origin {
+    /// Decodes the calldata of the OP Stack L1 attributes deposit transaction.
+    pub fn from_op_attributes(input: &[u8]) -> Option<Self> {
+        let selector: [u8; 4] = input.get(..4)?.try_into().ok()?;
+        match selector {
+            SET_L1_BLOCK_VALUES => Some(Self {
+                number: be_u64(input.get(4 + 24..4 + 32)?),
+                hash: Some(hex(input.get(4 + 3 * 32..4 + 4 * 32)?)),
+                sequence_number: Some(be_u64(input.get(4 + 4 * 32 + 24..4 + 5 * 32)?)),
+                send_count: None,
+            }),
+            SET_L1_BLOCK_VALUES_ECOTONE | SET_L1_BLOCK_VALUES_ISTHMUS => Some(Self {
+                number: be_u64(input.get(28..36)?),
+                hash: Some(hex(input.get(100..132)?)),
+                sequence_number: Some(be_u64(input.get(12..20)?)),
+                send_count: None,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn from_arbitrum_header(fields: &L2Fields) -> Option<Self> {
+        Some(Self {
+            number: fields.l1_block_number?.to::<u64>(),
+            hash: None,
+            sequence_number: None,
+            send_count: fields.send_count.map(|count| count.to::<u64>()),
+        })
+    }
+}
+
+fn be_u64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |acc, b| (acc << 8) | *b as u64)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::from("0x"), |out, b| out + &format!("{:02x}", b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{hex, keccak256};
+
+    const L1_HASH: &str = "0x1b1a6a0c1b8f86a33b0b8e4a4d6a3e4f2f2b0e6f0a7b8c9d0e1f2a3b4c5d6e7f";
+    const BATCHER_HASH: &str = "0000000000000000000000006887246668a3b87f54deb3b94ba47a6f63f32985";
+
+    /// `setL1BlockValuesEcotone` calldata, field by field as the L1 block contract packs it.
+    fn ecotone_calldata(selector: &str) -> Vec<u8> {
+        let calldata = [
+            selector,
+            "0000146b",         // baseFeeScalar
+            "000f79c5",         // blobBaseFeeScalar
+            "0000000000000003", // sequenceNumber
+            "00000000670ab2b7", // timestamp
+            "00000000013f2b4c", // number, 20917068
+            "00000000000000000000000000000000000000000000000000000001a13b8600", // basefee
+            "0000000000000000000000000000000000000000000000000000000000000001", // blobBaseFee
+            &L1_HASH[2..],
+            BATCHER_HASH,
+        ]
+        .concat();
+        hex::decode(calldata).unwrap()
+    }
+
+    #[test]
+    fn selectors_match_the_function_signatures() {
+        let selector =
+            |signature: &str| -> [u8; 4] { keccak256(signature)[..4].try_into().unwrap() };
+        assert_eq!(
+            selector(
+                "setL1BlockValues(uint64,uint64,uint256,bytes32,uint64,bytes32,uint256,uint256)"
+            ),
+            SET_L1_BLOCK_VALUES
+        );
+        assert_eq!(
+            selector("setL1BlockValuesEcotone()"),
+            SET_L1_BLOCK_VALUES_ECOTONE
+        );
+        assert_eq!(
+            selector("setL1BlockValuesIsthmus()"),
+            SET_L1_BLOCK_VALUES_ISTHMUS
+        );
+    }
+
+    #[test]
+    fn decodes_ecotone_attributes() {
+        let input = ecotone_calldata("440a5e20");
+        assert_eq!(input.len(), 164);
+        assert_eq!(
+            L1Origin::from_op_attributes(&input),
+            Some(L1Origin {
+                number: 20_917_068,
+                hash: Some(L1_HASH.to_string()),
+                sequence_number: Some(3),
+                send_count: None,
+            })
+        );
+    }
+
+    #[test]
+    fn decodes_isthmus_attributes() {
+        // operatorFeeScalar and operatorFeeConstant are appended to the Ecotone layout
+        let mut input = ecotone_calldata("098999be");
+        input.extend_from_slice(&[0; 12]);
+        assert_eq!(input.len(), 176);
+        let origin = L1Origin::from_op_attributes(&input).unwrap();
+        assert_eq!(origin.number, 20_917_068);
+        assert_eq!(origin.hash.as_deref(), Some(L1_HASH));
+    }
+
+    #[test]
+    fn decodes_bedrock_attributes() {
+        let word = |value: &str| format!("{:0>64}", value);
+        let calldata = [
+            "015d8eb9".to_string(),
+            word("1176583"),   // number
+            word("64e1b47b"),  // timestamp
+            word("4a817c800"), // basefee
+            L1_HASH[2..].to_string(),
+            word("5"), // sequenceNumber
+            BATCHER_HASH.to_string(),
+            word("bc"),    // l1FeeOverhead
+            word("a6fe0"), // l1FeeScalar
+        ]
+        .concat();
+        let input = hex::decode(calldata).unwrap();
+        assert_eq!(input.len(), 4 + 8 * 32);
+        assert_eq!(
+            L1Origin::from_op_attributes(&input),
+            Some(L1Origin {
+                number: 0x1176583,
+                hash: Some(L1_HASH.to_string()),
+                sequence_number: Some(5),
+                send_count: None,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_or_truncated_calldata() {
+        assert_eq!(L1Origin::from_op_attributes(&[]), None);
+        assert_eq!(L1Origin::from_op_attributes(&[0x44, 0x0a]), None);
+        assert_eq!(
+            L1Origin::from_op_attributes(&ecotone_calldata("a9059cbb")),
+            None
+        );
+        assert_eq!(
+            L1Origin::from_op_attributes(&ecotone_calldata("440a5e20")[..120]),
+            None
+        );
+    }
+
+    #[test]
+    fn system_transactions_by_chain() {
+        assert!(!Chain::L1.is_system_tx(0x7e));
+        assert!(Chain::OpStack.is_system_tx(0x7e));
+        assert!(!Chain::OpStack.is_system_tx(0x02));
+        assert!(Chain::Arbitrum.is_system_tx(0x64));
+        assert!(Chain::Arbitrum.is_system_tx(0x6a));
+        assert!(!Chain::Arbitrum.is_system_tx(0x7e));
+        assert_eq!("op-stack".parse::<Chain>().unwrap(), Chain::OpStack);
+        assert!("optimism".parse::<Chain>().is_err());
+    }
+}
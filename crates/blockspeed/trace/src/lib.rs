@@ -1,4 +1,22 @@
 #![allow(missing_docs)]
 
+pub mod block;
+pub mod buffer;
+pub mod chain;
+pub mod config;
+pub mod file;
+pub mod header;
+pub mod l2;
+pub mod line_protocol;
+pub mod mempool;
 pub mod output;
+pub mod prometheus;
+pub mod propagation;
+pub mod reconnect;
+pub mod report;
+pub mod sink;
+pub mod spool;
+pub mod status;
+pub mod throughput;
+pub mod tui;
 pub mod ws;
@@ -0,0 +1,174 @@
+#![allow(missing_docs)]
+
+use crate::chain::Reorg;
+use crate::output::{encode, Point, Schema};
+use crate::sink::{BlockMetrics, MetricsSink};
+use futures_util::future::BoxFuture;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use url::Url;
+
+/// Keeps UDP datagrams below a typical MTU so a batch isn't fragmented or silently dropped.
+const MAX_DATAGRAM_BYTES: usize = 1400;
+
+/// Credentials and retention policy for InfluxDB 1.x style `/write` endpoints.
+#[derive(Debug, Clone, Default)]
+pub struct V1Options {
+    pub db: Option<String>,
+    pub rp: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Debug)]
+enum Target {
+    Http { client: reqwest::Client, url: Url },
+    Udp { addr: String },
+}
+
+/// Writes the same measurements as [`Output`](crate::output::Output) as raw line protocol,
+/// either over HTTP to an InfluxDB 1.x compatible `/write` endpoint (InfluxDB 1.8,
+/// VictoriaMetrics, Telegraf `http_listener_v2`) or as UDP datagrams (Telegraf
+/// `socket_listener`, InfluxDB UDP input).
+#[derive(Debug)]
+pub struct LineProtocolSink {
+    target: Target,
+    options: V1Options,
+    schema: Schema,
+}
+
+impl LineProtocolSink {
+    /// `url` is either `http(s)://host:port[/path]`, `/write` is appended when no path is
+    /// given, or `udp://host:port`.
+    pub fn new(url: &str, options: V1Options) -> eyre::Result<Self> {
+        let mut url =
+            Url::parse(url).map_err(|e| eyre::eyre!("failed to parse URL {}: {}", url, e))?;
+
+        let target = match url.scheme() {
+            "http" | "https" => {
+                if url.path().is_empty() || url.path() == "/" {
+                    url.set_path("/write");
+                }
+                let client = reqwest::Client::builder()
+                    .timeout(Duration::from_secs(10))
+                    .build()?;
+                Target::Http { client, url }
+            }
+            "udp" => {
+                let host = url
+                    .host_str()
+                    .ok_or_else(|| eyre::eyre!("missing host in {}", url))?;
+                let port = url
+                    .port()
+                    .ok_or_else(|| eyre::eyre!("missing port in {}", url))?;
+                Target::Udp {
+                    addr: format!("{}:{}", host, port),
+                }
+            }
+            scheme => {
+                return Err(eyre::eyre!(
+                    "unsupported line protocol scheme '{}', expected http, https or udp",
+                    scheme
+                ))
+            }
+        };
+
+        Ok(Self {
+            target,
+            options,
+            schema: Schema::default(),
+        })
+    }
+
+    pub fn with_schema(mut self, schema: Schema) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    async fn write_points(&self, points: &[Point]) -> eyre::Result<()> {
+        let body = encode(points)?;
+        if body.is_empty() {
+            return Ok(());
+        }
+
+        match &self.target {
+            Target::Http { client, url } => {
+                let mut query = vec![("precision", "ns")];
+                if let Some(db) = self.options.db.as_deref() {
+                    query.push(("db", db));
+                }
+                if let Some(rp) = self.options.rp.as_deref() {
+                    query.push(("rp", rp));
+                }
+
+                let mut request = client.post(url.clone()).query(&query).body(body);
+                if let Some(username) = self.options.username.as_deref() {
+                    request = request.basic_auth(username, self.options.password.as_deref());
+                }
+
+                let response = request.send().await?;
+                if !response.status().is_success() {
+                    let status = response.status();
+                    let text = response.text().await.unwrap_or_default();
+                    return Err(eyre::eyre!(
+                        "line protocol write to {} failed with {}: {}",
+                        url,
+                        status,
+                        text
+                    ));
+                }
+                Ok(())
+            }
+            Target::Udp { addr } => {
+                let socket = UdpSocket::bind("0.0.0.0:0").await?;
+                socket.connect(addr).await?;
+                for datagram in datagrams(&body) {
+                    socket.send(datagram.as_bytes()).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Groups whole lines into datagrams of at most [`MAX_DATAGRAM_BYTES`], a single longer line
+/// gets a datagram of its own.
+fn datagrams(body: &str) -> Vec<String> {
+    let mut datagrams = Vec::new();
+    let mut current = String::new();
+    for line in body.lines().filter(|l| !l.is_empty()) {
+        if !current.is_empty() && current.len() + line.len() + 1 > MAX_DATAGRAM_BYTES {
+            datagrams.push(std::mem::take(&mut current));
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+    if !current.is_empty() {
+        datagrams.push(current);
+    }
+    datagrams
+}
+
+impl MetricsSink for LineProtocolSink {
+    fn write_blocks<'a>(&'a self, blocks: &'a [BlockMetrics]) -> BoxFuture<'a, eyre::Result<()>> {
+        Box::pin(async move {
+            let points: Vec<Point> = blocks
+                .iter()
+                .flat_map(|block| Point::from_block(block, self.schema))
+                .collect();
+            self.write_points(&points).await
+        })
+    }
+
+    fn write_reorg<'a>(
+        &'a self,
+        endpoint: &'a str,
+        reorg: &'a Reorg,
+        timestamp_nanos: u64,
+    ) -> BoxFuture<'a, eyre::Result<()>> {
+        Box::pin(async move {
+            let points = Point::from_reorg(endpoint, reorg, timestamp_nanos, self.schema);
+            self.write_points(&points).await
+        })
+    }
+}
@@ -0,0 +1,109 @@
+#![allow(missing_docs)]
+
+use crate::block::percentile;
+use alloy_primitives::B256;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Pending hashes not included after this long are forgotten, they were most likely dropped
+/// or replaced.
+const PENDING_TTL_NANOS: u64 = 30 * 60 * 1_000_000_000;
+/// Most pending hashes tracked per endpoint, new ones are ignored until some are included.
+const MAX_PENDING: usize = 200_000;
+
+/// What an endpoint's mempool looked like up to a block.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MempoolStats {
+    /// pending hashes first seen since the previous block, per second
+    pub inflow_tps: f64,
+    /// time from first seeing a transaction pending to the block arriving, sorted, for the
+    /// transactions of the block that were seen pending
+    pub inclusion_ms: Vec<f64>,
+    /// pending hashes still waiting for inclusion
+    pub pending: u64,
+}
+
+impl MempoolStats {
+    /// Nearest-rank percentile of the inclusion latencies, `None` when no transaction of the
+    /// block was seen pending.
+    pub fn inclusion_percentile(&self, pct: f64) -> Option<f64> {
+        (!self.inclusion_ms.is_empty()).then(|| percentile(&self.inclusion_ms, pct))
+    }
+}
+
+#[derive(Debug)]
+struct Pending {
+    first_seen: HashMap<B256, u64>,
+    seen_since_block: u64,
+    last_block_nanos: u64,
+}
+
+/// First-seen times of the `newPendingTransactions` hashes of a single endpoint, matched
+/// against the transactions of each new block.
+#[derive(Debug)]
+pub struct MempoolTracker {
+    pending: Mutex<Pending>,
+}
+
+impl MempoolTracker {
+    pub fn new(started_nanos: u64) -> Self {
+        Self {
+            pending: Mutex::new(Pending {
+                first_seen: HashMap::new(),
+                seen_since_block: 0,
+                last_block_nanos: started_nanos,
+            }),
+        }
+    }
+
+    /// Returns whether the hash was new, hashes announced again keep their first-seen time.
+    pub fn record_pending(&self, hash: B256, seen_nanos: u64) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.first_seen.len() >= MAX_PENDING || pending.first_seen.contains_key(&hash) {
+            return false;
+        }
+        pending.first_seen.insert(hash, seen_nanos);
+        pending.seen_since_block += 1;
+        true
+    }
+
+    /// Takes the included transactions out of the pending set and measures them against the
+    /// block's arrival.
+    pub fn record_block(&self, tx_hashes: &[B256], arrival_nanos: u64) -> MempoolStats {
+        let mut pending = self.pending.lock().unwrap();
+
+        let mut inclusion_ms: Vec<f64> = tx_hashes
+            .iter()
+            .filter_map(|hash| pending.first_seen.remove(hash))
+            .map(|seen_nanos| arrival_nanos.saturating_sub(seen_nanos) as f64 / 1_000_000.0)
+            .collect();
+        inclusion_ms.sort_unstable_by(f64::total_cmp);
+
+        let diff_nanos = arrival_nanos.saturating_sub(pending.last_block_nanos);
+        let inflow_tps = if diff_nanos > 0 {
+            pending.seen_since_block as f64 / (diff_nanos as f64 / 1_000_000_000.0)
+        } else {
+            0.0
+        };
+        pending.seen_since_block = 0;
+        pending.last_block_nanos = arrival_nanos;
+
+        pending
+            .first_seen
+            .retain(|_, seen_nanos| arrival_nanos.saturating_sub(*seen_nanos) < PENDING_TTL_NANOS);
+
+        MempoolStats {
+            inflow_tps,
+            inclusion_ms,
+            pending: pending.first_seen.len() as u64,
+        }
+    }
+
+    /// Drops the transactions of a backfilled block, their arrival time means nothing.
+    pub fn forget(&self, tx_hashes: &[B256]) {
+        let mut pending = self.pending.lock().unwrap();
+        for hash in tx_hashes {
+            pending.first_seen.remove(hash);
+        }
+    }
+}
@@ -1,18 +1,27 @@
 #![allow(missing_docs)]
 
-use futures_util::stream;
-use influxdb2::models::WriteDataPoint;
+use crate::chain::Reorg;
+use crate::sink::{BlockMetrics, MetricsSink};
+use crate::spool::Spool;
+use futures_util::future::BoxFuture;
+use influxdb2::models::{DataPoint, WriteDataPoint};
 use influxdb2::Client;
 use influxdb2_derive::WriteDataPoint;
+use metrics::{counter, describe_counter, describe_histogram, histogram, Unit};
 use std::{
-    future::Future,
+    io,
     sync::{Arc, Mutex},
 };
-use tokio::time::{sleep, Duration};
+use tokio::sync::Mutex as AsyncMutex;
+use tokio::time::{sleep, Duration, Instant};
 use tracing::{error, info, warn};
 
 const MAX_RECONNECT_ATTEMPTS: u32 = 5;
 const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(500);
+const REPLAY_BATCH_SIZE: usize = 5_000;
+/// While InfluxDB is known to be unreachable points go straight to the spool, it is only
+/// probed again once this long has passed since the last failure.
+const SPOOL_PROBE_INTERVAL: Duration = Duration::from_secs(30);
 
 #[derive(Debug)]
 pub struct Output {
@@ -21,9 +30,13 @@ pub struct Output {
     url: String,
     token: String,
     org: String,
+    spool: Option<AsyncMutex<Spool>>,
+    /// last time a write found InfluxDB unreachable, cleared by the next successful one
+    unreachable_at: Mutex<Option<Instant>>,
+    schema: Schema,
 }
 
-#[derive(Debug, Default, WriteDataPoint)]
+#[derive(Debug, Default, Clone, WriteDataPoint)]
 #[measurement = "blockspeed_mgas"]
 pub struct Mgas {
     #[influxdb(tag)]
@@ -36,7 +49,7 @@ pub struct Mgas {
     timestamp: u64,
 }
 
-#[derive(Debug, Default, WriteDataPoint)]
+#[derive(Debug, Default, Clone, WriteDataPoint)]
 #[measurement = "blockspeed_tps"]
 pub struct Tps {
     #[influxdb(tag)]
@@ -49,7 +62,7 @@ pub struct Tps {
     timestamp: u64,
 }
 
-#[derive(Debug, Default, WriteDataPoint)]
+#[derive(Debug, Default, Clone, WriteDataPoint)]
 #[measurement = "blockspeed_gas_used"]
 pub struct GasUsed {
     #[influxdb(tag)]
@@ -62,7 +75,7 @@ pub struct GasUsed {
     timestamp: u64,
 }
 
-#[derive(Debug, Default, WriteDataPoint)]
+#[derive(Debug, Default, Clone, WriteDataPoint)]
 #[measurement = "blockspeed_txs"]
 pub struct Txs {
     #[influxdb(tag)]
@@ -75,7 +88,7 @@ pub struct Txs {
     timestamp: u64,
 }
 
-#[derive(Debug, Default, WriteDataPoint)]
+#[derive(Debug, Default, Clone, WriteDataPoint)]
 #[measurement = "blockspeed_block_per_sec"]
 pub struct BlockPerSec {
     #[influxdb(tag)]
@@ -86,8 +99,461 @@ pub struct BlockPerSec {
     timestamp: u64,
 }
 
+#[derive(Debug, Default, Clone, WriteDataPoint)]
+#[measurement = "blockspeed_block_stats"]
+pub struct Stats {
+    #[influxdb(tag)]
+    endpoint: String,
+    #[influxdb(tag)]
+    block_hash: String,
+    #[influxdb(field)]
+    gas_limit: f64,
+    #[influxdb(field)]
+    gas_utilisation: f64,
+    #[influxdb(field)]
+    base_fee_gwei: f64,
+    #[influxdb(field)]
+    blob_gas_used: f64,
+    #[influxdb(field)]
+    priority_fee_p50_gwei: f64,
+    #[influxdb(field)]
+    priority_fee_p90_gwei: f64,
+    #[influxdb(field)]
+    priority_fee_p99_gwei: f64,
+    #[influxdb(field)]
+    legacy_txs: f64,
+    #[influxdb(field)]
+    access_list_txs: f64,
+    #[influxdb(field)]
+    eip1559_txs: f64,
+    #[influxdb(field)]
+    blob_txs: f64,
+    #[influxdb(field)]
+    set_code_txs: f64,
+    #[influxdb(field)]
+    other_txs: f64,
+    #[influxdb(field)]
+    contract_creations: f64,
+    #[influxdb(timestamp)]
+    timestamp: u64,
+}
+
+#[derive(Debug, Default, Clone, WriteDataPoint)]
+#[measurement = "blockspeed_rolling"]
+pub struct Rolling {
+    #[influxdb(tag)]
+    endpoint: String,
+    #[influxdb(tag)]
+    window: String,
+    #[influxdb(field)]
+    mgas: f64,
+    #[influxdb(field)]
+    tps: f64,
+    #[influxdb(field)]
+    block_time: f64,
+    #[influxdb(timestamp)]
+    timestamp: u64,
+}
+
+#[derive(Debug, Default, Clone, WriteDataPoint)]
+#[measurement = "blockspeed_propagation"]
+pub struct Propagation {
+    #[influxdb(tag)]
+    endpoint: String,
+    #[influxdb(tag)]
+    first_endpoint: String,
+    #[influxdb(tag)]
+    block_hash: String,
+    #[influxdb(field)]
+    block_number: f64,
+    #[influxdb(field)]
+    delay_ms: f64,
+    #[influxdb(timestamp)]
+    timestamp: u64,
+}
+
+#[derive(Debug, Default, Clone, WriteDataPoint)]
+#[measurement = "blockspeed_reorg"]
+pub struct ReorgPoint {
+    #[influxdb(tag)]
+    endpoint: String,
+    #[influxdb(field)]
+    depth: f64,
+    #[influxdb(field)]
+    common_ancestor: f64,
+    #[influxdb(field)]
+    new_head: f64,
+    #[influxdb(field)]
+    orphaned_hashes: String,
+    #[influxdb(field)]
+    reemitted_hashes: String,
+    #[influxdb(timestamp)]
+    timestamp: u64,
+}
+
+#[derive(Debug, Default, Clone, WriteDataPoint)]
+#[measurement = "blockspeed_orphaned"]
+pub struct Orphaned {
+    #[influxdb(tag)]
+    endpoint: String,
+    #[influxdb(tag)]
+    block_hash: String,
+    #[influxdb(field)]
+    block_number: f64,
+    #[influxdb(field)]
+    orphaned: bool,
+    #[influxdb(timestamp)]
+    timestamp: u64,
+}
+
+/// Which measurements a block is written as.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Schema {
+    /// one `blockspeed_block` point per block, number and hash are fields
+    #[default]
+    Block,
+    /// the original per-value measurements (`blockspeed_mgas`, `blockspeed_tps`, ...) tagged
+    /// with `block_hash`. Those that predate named endpoints keep their `uri` tag.
+    Legacy,
+}
+
+/// Any of the points above, so that all points of a block go out in a single write.
+#[derive(Debug, Clone)]
+pub enum Point {
+    Block(DataPoint),
+    Mgas(Mgas),
+    Tps(Tps),
+    GasUsed(GasUsed),
+    Txs(Txs),
+    BlockPerSec(BlockPerSec),
+    Stats(Stats),
+    Rolling(Rolling),
+    Propagation(Propagation),
+    Reorg(ReorgPoint),
+    Orphaned(Orphaned),
+}
+
+impl WriteDataPoint for Point {
+    fn write_data_point_to<W>(&self, w: W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        match self {
+            Self::Block(p) => p.write_data_point_to(w),
+            Self::Mgas(p) => p.write_data_point_to(w),
+            Self::Tps(p) => p.write_data_point_to(w),
+            Self::GasUsed(p) => p.write_data_point_to(w),
+            Self::Txs(p) => p.write_data_point_to(w),
+            Self::BlockPerSec(p) => p.write_data_point_to(w),
+            Self::Stats(p) => p.write_data_point_to(w),
+            Self::Rolling(p) => p.write_data_point_to(w),
+            Self::Propagation(p) => p.write_data_point_to(w),
+            Self::Reorg(p) => p.write_data_point_to(w),
+            Self::Orphaned(p) => p.write_data_point_to(w),
+        }
+    }
+}
+
+impl Point {
+    pub fn from_block(block: &BlockMetrics, schema: Schema) -> Vec<Self> {
+        match schema {
+            Schema::Block => Self::block_points(block),
+            Schema::Legacy => Self::legacy_points(block),
+        }
+    }
+
+    /// Everything about a block as fields of a single `blockspeed_block` point, tagged by
+    /// endpoint, fee recipient and builder. Rolling rates keep their own measurement as they
+    /// are tagged by window.
+    fn block_points(block: &BlockMetrics) -> Vec<Self> {
+        let timestamp = block.timestamp_nanos;
+        let mut builder =
+            DataPoint::builder("blockspeed_block").tag("endpoint", block.endpoint.as_str());
+        if !block.fee_recipient.is_empty() {
+            builder = builder.tag("fee_recipient", block.fee_recipient.as_str());
+        }
+        if let Some(tag) = &block.builder {
+            builder = builder.tag("builder", tag.as_str());
+        }
+        let mut builder = builder
+            .field("number", block.number as f64)
+            .field("hash", block.hash.as_str())
+            .field("block_timestamp", block.block_timestamp as f64)
+            .field("backfilled", block.backfilled)
+            .field("gas_used", block.gas_used as f64)
+            .field("tx_count", block.tx_count as f64)
+            .timestamp(timestamp as i64);
+
+        if let Some(rates) = &block.rates {
+            builder = builder
+                .field("mgas", rates.mgas)
+                .field("tps", rates.tps)
+                .field("block_time", rates.block_time);
+        }
+
+        if block.system_tx_count > 0 {
+            builder = builder.field("system_tx_count", block.system_tx_count as f64);
+        }
+        if let Some(origin) = &block.l1_origin {
+            builder = builder.field("l1_block_number", origin.number as f64);
+            if let Some(hash) = &origin.hash {
+                builder = builder.field("l1_block_hash", hash.as_str());
+            }
+            if let Some(sequence_number) = origin.sequence_number {
+                builder = builder.field("l1_sequence_number", sequence_number as f64);
+            }
+            if let Some(send_count) = origin.send_count {
+                builder = builder.field("l1_send_count", send_count as f64);
+            }
+        }
+
+        if let Some(delay) = &block.propagation {
+            builder = builder
+                .field("first_endpoint", delay.first_endpoint.as_str())
+                .field(
+                    "propagation_delay_ms",
+                    delay.delay_nanos as f64 / 1_000_000.0,
+                );
+        }
+
+        if let Some(mempool) = &block.mempool {
+            builder = builder
+                .field("mempool_inflow_tps", mempool.inflow_tps)
+                .field("mempool_pending", mempool.pending as f64)
+                .field("inclusion_seen_txs", mempool.inclusion_ms.len() as f64);
+            for (field, pct) in [
+                ("inclusion_p50_ms", 50.0),
+                ("inclusion_p95_ms", 95.0),
+                ("inclusion_p99_ms", 99.0),
+                ("inclusion_max_ms", 100.0),
+            ] {
+                if let Some(latency) = mempool.inclusion_percentile(pct) {
+                    builder = builder.field(field, latency);
+                }
+            }
+        }
+
+        let fee_market = &block.fee_market;
+        builder = builder
+            .field("gas_limit", fee_market.gas_limit as f64)
+            .field("gas_utilisation", fee_market.gas_utilisation);
+        if let Some(base_fee) = fee_market.base_fee_gwei {
+            builder = builder.field("base_fee_gwei", base_fee);
+        }
+        if let Some(blob_gas_used) = fee_market.blob_gas_used {
+            builder = builder.field("blob_gas_used", blob_gas_used as f64);
+        }
+        if let Some(excess_blob_gas) = fee_market.excess_blob_gas {
+            builder = builder.field("excess_blob_gas", excess_blob_gas as f64);
+        }
+        if let Some(blob_base_fee) = fee_market.blob_base_fee_gwei {
+            builder = builder.field("blob_base_fee_gwei", blob_base_fee);
+        }
+
+        if let Some(stats) = &block.stats {
+            builder = builder
+                .field("priority_fee_p50_gwei", stats.priority_fee_p50_gwei)
+                .field("priority_fee_p90_gwei", stats.priority_fee_p90_gwei)
+                .field("priority_fee_p99_gwei", stats.priority_fee_p99_gwei)
+                .field("legacy_txs", stats.legacy_txs as f64)
+                .field("access_list_txs", stats.access_list_txs as f64)
+                .field("eip1559_txs", stats.eip1559_txs as f64)
+                .field("blob_txs", stats.blob_txs as f64)
+                .field("set_code_txs", stats.set_code_txs as f64)
+                .field("other_txs", stats.other_txs as f64)
+                .field("contract_creations", stats.contract_creations as f64);
+        }
+
+        let mut points = vec![Self::Block(
+            builder.build().expect("block point has fields"),
+        )];
+        points.extend(Self::rolling_points(block));
+        points
+    }
+
+    fn rolling_points(block: &BlockMetrics) -> impl Iterator<Item = Self> + '_ {
+        block.rolling.iter().map(|(window, rates)| {
+            Self::Rolling(Rolling {
+                endpoint: block.endpoint.clone(),
+                window: window.clone(),
+                mgas: rates.mgas,
+                tps: rates.tps,
+                block_time: rates.block_time,
+                timestamp: block.timestamp_nanos,
+            })
+        })
+    }
+
+    fn legacy_points(block: &BlockMetrics) -> Vec<Self> {
+        let endpoint = &block.endpoint;
+        let block_hash = &block.hash;
+        let timestamp = block.timestamp_nanos;
+        let mut points = Vec::new();
+
+        if let Some(rates) = &block.rates {
+            points.push(Self::Mgas(Mgas {
+                uri: endpoint.clone(),
+                block_hash: block_hash.clone(),
+                value: rates.mgas,
+                timestamp,
+            }));
+            points.push(Self::Tps(Tps {
+                uri: endpoint.clone(),
+                block_hash: block_hash.clone(),
+                value: rates.tps,
+                timestamp,
+            }));
+        }
+
+        points.extend(Self::rolling_points(block));
+
+        points.push(Self::GasUsed(GasUsed {
+            uri: endpoint.clone(),
+            block_hash: block_hash.clone(),
+            value: block.gas_used as f64,
+            timestamp,
+        }));
+        points.push(Self::Txs(Txs {
+            uri: endpoint.clone(),
+            block_hash: block_hash.clone(),
+            value: block.tx_count as f64,
+            timestamp,
+        }));
+        points.push(Self::BlockPerSec(BlockPerSec {
+            uri: endpoint.clone(),
+            value: 1.0,
+            timestamp,
+        }));
+
+        // header values exist for every block, unlike `blockspeed_block_stats`
+        let fee_market = &block.fee_market;
+        let mut builder = DataPoint::builder("blockspeed_fee_market")
+            .tag("endpoint", endpoint.as_str())
+            .tag("block_hash", block_hash.as_str())
+            .field("gas_limit", fee_market.gas_limit as f64)
+            .field("gas_utilisation", fee_market.gas_utilisation)
+            .timestamp(timestamp as i64);
+        if let Some(base_fee) = fee_market.base_fee_gwei {
+            builder = builder.field("base_fee_gwei", base_fee);
+        }
+        if let Some(blob_gas_used) = fee_market.blob_gas_used {
+            builder = builder.field("blob_gas_used", blob_gas_used as f64);
+        }
+        if let Some(excess_blob_gas) = fee_market.excess_blob_gas {
+            builder = builder.field("excess_blob_gas", excess_blob_gas as f64);
+        }
+        if let Some(blob_base_fee) = fee_market.blob_base_fee_gwei {
+            builder = builder.field("blob_base_fee_gwei", blob_base_fee);
+        }
+        points.push(Self::Block(
+            builder.build().expect("fee market point has fields"),
+        ));
+
+        if let Some(delay) = &block.propagation {
+            points.push(Self::Propagation(Propagation {
+                endpoint: endpoint.clone(),
+                first_endpoint: delay.first_endpoint.clone(),
+                block_hash: block_hash.clone(),
+                block_number: block.number as f64,
+                delay_ms: delay.delay_nanos as f64 / 1_000_000.0,
+                timestamp,
+            }));
+        }
+
+        if let Some(stats) = &block.stats {
+            points.push(Self::Stats(Stats {
+                endpoint: endpoint.clone(),
+                block_hash: block_hash.clone(),
+                gas_limit: block.fee_market.gas_limit as f64,
+                gas_utilisation: block.fee_market.gas_utilisation,
+                base_fee_gwei: block.fee_market.base_fee_gwei.unwrap_or_default(),
+                blob_gas_used: block.fee_market.blob_gas_used.unwrap_or_default() as f64,
+                priority_fee_p50_gwei: stats.priority_fee_p50_gwei,
+                priority_fee_p90_gwei: stats.priority_fee_p90_gwei,
+                priority_fee_p99_gwei: stats.priority_fee_p99_gwei,
+                legacy_txs: stats.legacy_txs as f64,
+                access_list_txs: stats.access_list_txs as f64,
+                eip1559_txs: stats.eip1559_txs as f64,
+                blob_txs: stats.blob_txs as f64,
+                set_code_txs: stats.set_code_txs as f64,
+                other_txs: stats.other_txs as f64,
+                contract_creations: stats.contract_creations as f64,
+                timestamp,
+            }));
+        }
+
+        points
+    }
+
+    /// The reorg itself plus a marker at the original point timestamp of every orphaned block,
+    /// so its data points can be filtered out or joined against.
+    pub fn from_reorg(endpoint: &str, reorg: &Reorg, timestamp: u64, schema: Schema) -> Vec<Self> {
+        let mut points = vec![Self::Reorg(ReorgPoint {
+            endpoint: endpoint.to_string(),
+            depth: reorg.depth() as f64,
+            common_ancestor: reorg.common_ancestor.number as f64,
+            new_head: reorg.new_head as f64,
+            orphaned_hashes: reorg
+                .orphaned
+                .iter()
+                .map(|b| b.hash.as_str())
+                .collect::<Vec<_>>()
+                .join(","),
+            reemitted_hashes: reorg.reemitted.join(","),
+            timestamp,
+        })];
+
+        points.extend(reorg.orphaned.iter().map(|block| {
+            match schema {
+                // same series and timestamp as the original block point, so the field merges into
+                // it. Only `orphaned` is written, the block's own fields are already there.
+                Schema::Block => {
+                    let mut builder =
+                        DataPoint::builder("blockspeed_block").tag("endpoint", endpoint);
+                    if !block.fee_recipient.is_empty() {
+                        builder = builder.tag("fee_recipient", block.fee_recipient.as_str());
+                    }
+                    if let Some(tag) = &block.builder {
+                        builder = builder.tag("builder", tag.as_str());
+                    }
+                    Self::Block(
+                        builder
+                            .field("orphaned", true)
+                            .timestamp(block.timestamp_nanos as i64)
+                            .build()
+                            .expect("orphaned point has fields"),
+                    )
+                }
+                Schema::Legacy => Self::Orphaned(Orphaned {
+                    endpoint: endpoint.to_string(),
+                    block_hash: block.hash.clone(),
+                    block_number: block.number as f64,
+                    orphaned: true,
+                    timestamp: block.timestamp_nanos,
+                }),
+            }
+        }));
+
+        points
+    }
+}
+
 impl Output {
     pub fn new(url: &str, token: &str, org: &str, bucket: &str) -> Self {
+        describe_histogram!(
+            "blockspeed_influxdb_write_seconds",
+            Unit::Seconds,
+            "duration of each InfluxDB write attempt"
+        );
+        describe_counter!(
+            "blockspeed_influxdb_write_failures",
+            "InfluxDB writes that failed after all retries"
+        );
+        describe_counter!(
+            "blockspeed_influxdb_write_retries",
+            "InfluxDB writes retried after a connection error"
+        );
+
         let client = Client::new(url, org, token);
 
         Self {
@@ -96,33 +562,135 @@ impl Output {
             url: url.to_string(),
             token: token.to_string(),
             org: org.to_string(),
+            spool: None,
+            unreachable_at: Mutex::new(None),
+            schema: Schema::default(),
+        }
+    }
+
+    pub fn with_schema(mut self, schema: Schema) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// Points that can't be written because InfluxDB is unreachable go to `spool` and are
+    /// replayed, oldest first, ahead of the next successful write. Until InfluxDB is probed
+    /// again, every [`SPOOL_PROBE_INTERVAL`], new points are spooled without trying.
+    pub fn with_spool(mut self, spool: Spool) -> Self {
+        self.spool = Some(AsyncMutex::new(spool));
+        self
+    }
+
+    async fn write_points(&self, operation_name: &str, points: &[Point]) -> eyre::Result<()> {
+        let body = encode(points)?;
+
+        let Some(spool) = &self.spool else {
+            return self
+                .write_with_retry(operation_name, body, MAX_RECONNECT_ATTEMPTS)
+                .await
+                .map_err(WriteError::into_report);
+        };
+
+        // held across replay and write so points reach InfluxDB in the order they were produced
+        let mut spool = spool.lock().await;
+
+        // known to be down, don't stall the writer on retries until it's time to probe again
+        let unreachable_at = *self.unreachable_at.lock().unwrap();
+        let max_retries = match unreachable_at {
+            Some(at) if at.elapsed() < SPOOL_PROBE_INTERVAL => {
+                spool.append(&body).await?;
+                return Ok(());
+            }
+            // probe with a single attempt
+            Some(_) => 0,
+            None => MAX_RECONNECT_ATTEMPTS,
+        };
+
+        if !spool.is_empty() {
+            if let Err(e) = self.replay(&mut spool, max_retries).await {
+                warn!("spool replay stopped: {}", e);
+                *self.unreachable_at.lock().unwrap() = Some(Instant::now());
+                spool.append(&body).await?;
+                return Ok(());
+            }
+        }
+
+        match self
+            .write_with_retry(operation_name, body.clone(), max_retries)
+            .await
+        {
+            Ok(()) => {
+                *self.unreachable_at.lock().unwrap() = None;
+                Ok(())
+            }
+            Err(WriteError::Unreachable(e)) => {
+                warn!(
+                    "spooling {} points after write failure: {}",
+                    points.len(),
+                    e
+                );
+                *self.unreachable_at.lock().unwrap() = Some(Instant::now());
+                spool.append(&body).await?;
+                Ok(())
+            }
+            Err(WriteError::Rejected(e)) => {
+                *self.unreachable_at.lock().unwrap() = None;
+                Err(e)
+            }
+        }
+    }
+
+    async fn replay(&self, spool: &mut Spool, max_retries: u32) -> eyre::Result<()> {
+        let lines = spool.read().await?;
+        info!("replaying {} spooled points", lines.len());
+
+        let (mut done, mut replayed) = (0, 0);
+        for chunk in lines.chunks(REPLAY_BATCH_SIZE) {
+            let mut body = chunk.join("\n");
+            body.push('\n');
+            match self.write_with_retry("SPOOL", body, max_retries).await {
+                Ok(()) => replayed += chunk.len(),
+                // unreachable again, keep the rest for the next attempt
+                Err(WriteError::Unreachable(e)) => {
+                    spool.retain(&lines[done..], replayed).await?;
+                    return Err(e);
+                }
+                // InfluxDB will never accept these, don't let them block the spool
+                Err(WriteError::Rejected(e)) => {
+                    error!("dropping {} spooled points: {}", chunk.len(), e);
+                    counter!("blockspeed_spool_rejected").increment(chunk.len() as u64);
+                }
+            }
+            done += chunk.len();
         }
+
+        spool.retain(&[], replayed).await
     }
 
-    async fn write_with_retry<T, F, Fut>(
+    async fn write_with_retry(
         &self,
         operation_name: &str,
-        create_points: F,
-    ) -> Result<(), Box<dyn std::error::Error>>
-    where
-        F: Fn() -> Fut,
-        Fut: Future<Output = Vec<T>>,
-        T: WriteDataPoint + Send + Sync + 'static,
-    {
+        body: String,
+        max_retries: u32,
+    ) -> Result<(), WriteError> {
         let mut retry_count = 0;
         let mut retry_delay = INITIAL_RETRY_DELAY;
 
         loop {
-            let points = create_points().await;
-            let stream = stream::iter(points);
-
             // clone the client reference for this attempt
             let client = {
                 let client_guard = self.client.lock().unwrap();
                 client_guard.clone()
             };
 
-            match client.write(&self.bucket, stream).await {
+            let started = Instant::now();
+            let result = client
+                .write_line_protocol(&self.org, &self.bucket, body.clone())
+                .await;
+            histogram!("blockspeed_influxdb_write_seconds", "url" => self.url.clone())
+                .record(started.elapsed().as_secs_f64());
+
+            match result {
                 Ok(_) => return Ok(()),
                 Err(e) => {
                     let err_str = e.to_string();
@@ -133,17 +701,25 @@ impl Output {
                         || err_str.contains("Connection reset")
                         || err_str.contains("Broken pipe");
 
-                    if !is_connection_error || retry_count >= MAX_RECONNECT_ATTEMPTS {
+                    if !is_connection_error || retry_count >= max_retries {
+                        counter!("blockspeed_influxdb_write_failures", "url" => self.url.clone())
+                            .increment(1);
                         error!(
                             "failed to write {} to InfluxDB after {} attempts: {}",
                             operation_name,
                             retry_count + 1,
                             e
                         );
-                        return Err(e.into());
+                        return Err(if is_connection_error {
+                            WriteError::Unreachable(e.into())
+                        } else {
+                            WriteError::Rejected(e.into())
+                        });
                     }
 
                     retry_count += 1;
+                    counter!("blockspeed_influxdb_write_retries", "url" => self.url.clone())
+                        .increment(1);
                     warn!(
                         "failed to write {} to InfluxDB (attempt {}): {}, retrying in {:?}",
                         operation_name, retry_count, e, retry_delay
@@ -165,92 +741,111 @@ impl Output {
             }
         }
     }
+}
 
-    pub async fn write_mgas(
-        &self,
-        uri: &str,
-        block_hash: &str,
-        value: f64,
-        timestamp: u64,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        self.write_with_retry("MGAS", || async {
-            vec![Mgas {
-                uri: uri.to_string(),
-                block_hash: block_hash.to_string(),
-                value,
-                timestamp,
-            }]
-        })
-        .await
+enum WriteError {
+    /// InfluxDB couldn't be reached, worth spooling and trying again later
+    Unreachable(eyre::Report),
+    /// InfluxDB answered and refused the points
+    Rejected(eyre::Report),
+}
+
+impl WriteError {
+    fn into_report(self) -> eyre::Report {
+        match self {
+            Self::Unreachable(e) | Self::Rejected(e) => e,
+        }
+    }
+}
+
+/// Point timestamp of a block written at its block time. Blocks sharing a header second (L2s)
+/// would otherwise land on the same series and timestamp and overwrite each other, so the
+/// block number is added as a sub-second offset.
+pub fn block_point_nanos(block_timestamp: u64, number: u64) -> u64 {
+    block_timestamp * 1_000_000_000 + number % 1_000_000_000
+}
+
+/// Encodes points as line protocol, one point per line.
+pub(crate) fn encode(points: &[Point]) -> eyre::Result<String> {
+    let mut buf = Vec::new();
+    for point in points {
+        point.write_data_point_to(&mut buf)?;
     }
+    Ok(String::from_utf8(buf)?)
+}
 
-    pub async fn write_tps(
-        &self,
-        uri: &str,
-        block_hash: &str,
-        value: f64,
-        timestamp: u64,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        self.write_with_retry("TPS", || async {
-            vec![Tps {
-                uri: uri.to_string(),
-                block_hash: block_hash.to_string(),
-                value,
-                timestamp,
-            }]
+impl MetricsSink for Output {
+    fn write_blocks<'a>(&'a self, blocks: &'a [BlockMetrics]) -> BoxFuture<'a, eyre::Result<()>> {
+        Box::pin(async move {
+            let points: Vec<Point> = blocks
+                .iter()
+                .flat_map(|block| Point::from_block(block, self.schema))
+                .collect();
+            self.write_points("BLOCKS", &points).await
         })
-        .await
     }
 
-    pub async fn write_gas_used(
-        &self,
-        uri: &str,
-        block_hash: &str,
-        value: f64,
-        timestamp: u64,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        self.write_with_retry("GAS_USED", || async {
-            vec![GasUsed {
-                uri: uri.to_string(),
-                block_hash: block_hash.to_string(),
-                value,
-                timestamp,
-            }]
+    fn write_reorg<'a>(
+        &'a self,
+        endpoint: &'a str,
+        reorg: &'a Reorg,
+        timestamp_nanos: u64,
+    ) -> BoxFuture<'a, eyre::Result<()>> {
+        Box::pin(async move {
+            let points = Point::from_reorg(endpoint, reorg, timestamp_nanos, self.schema);
+            self.write_points("REORG", &points).await
         })
-        .await
     }
+}
 
-    pub async fn write_txs(
-        &self,
-        uri: &str,
-        block_hash: &str,
-        value: f64,
-        timestamp: u64,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        self.write_with_retry("TXS", || async {
-            vec![Txs {
-                uri: uri.to_string(),
-                block_hash: block_hash.to_string(),
-                value,
-                timestamp,
-            }]
-        })
-        .await
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn same_second_block(number: u64) -> BlockMetrics {
+        BlockMetrics {
+            endpoint: "arb".to_string(),
+            number,
+            hash: format!("0x{:064x}", number),
+            block_timestamp: 1_700_000_000,
+            timestamp_nanos: block_point_nanos(1_700_000_000, number),
+            backfilled: true,
+            fee_recipient: "0xa4b000000000000000000073657175656e636572".to_string(),
+            rolling: vec![("1m".to_string(), Default::default())],
+            ..Default::default()
+        }
     }
 
-    pub async fn write_block_per_sec(
-        &self,
-        uri: &str,
-        value: f64,
-        timestamp: u64,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        self.write_with_retry("BLOCK_PER_SEC", || async {
-            vec![BlockPerSec {
-                uri: uri.to_string(),
-                value,
-                timestamp,
-            }]
-        })
-        .await
+    #[test]
+    fn same_second_blocks_encode_as_distinct_points() {
+        for schema in [Schema::Block, Schema::Legacy] {
+            let first = encode(&Point::from_block(&same_second_block(100), schema)).unwrap();
+            let second = encode(&Point::from_block(&same_second_block(101), schema)).unwrap();
+
+            // series key plus timestamp of every line, the fields are what gets overwritten
+            let keys = |body: &str| -> Vec<String> {
+                body.lines()
+                    .map(|line| {
+                        let (series, rest) = line.split_once(' ').unwrap();
+                        let timestamp = rest.rsplit_once(' ').unwrap().1;
+                        format!("{} {}", series, timestamp)
+                    })
+                    .collect()
+            };
+            let first_keys = keys(&first);
+            for key in keys(&second) {
+                assert!(!first_keys.contains(&key), "{:?} collides: {}", schema, key);
+            }
+        }
+    }
+
+    #[test]
+    fn block_point_nanos_stays_within_the_block_second() {
+        assert_eq!(
+            block_point_nanos(1_700_000_000, 0),
+            1_700_000_000_000_000_000
+        );
+        let nanos = block_point_nanos(1_700_000_000, 250_000_123);
+        assert_eq!(nanos / 1_000_000_000, 1_700_000_000);
     }
 }
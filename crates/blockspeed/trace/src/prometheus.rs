@@ -0,0 +1,237 @@
+#![allow(missing_docs)]
+
+use crate::chain::Reorg;
+use crate::sink::{BlockMetrics, MetricsSink};
+use futures_util::future::BoxFuture;
+use metrics::{
+    counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram, Unit,
+};
+
+/// Exports the latest block of every endpoint as gauges and the per-block values as histograms
+/// through the process wide `metrics` recorder, i.e. on the metrics port.
+#[derive(Debug)]
+pub struct PrometheusSink;
+
+impl PrometheusSink {
+    pub fn new() -> Self {
+        describe_gauge!("blockspeed_block_number", "number of the last block");
+        describe_gauge!(
+            "blockspeed_block_gas_used",
+            Unit::Count,
+            "gas used by the last block"
+        );
+        describe_gauge!(
+            "blockspeed_block_txs",
+            Unit::Count,
+            "transactions in the last block"
+        );
+        describe_gauge!(
+            "blockspeed_mgas",
+            "million gas per second of the last block"
+        );
+        describe_gauge!(
+            "blockspeed_tps",
+            "transactions per second of the last block"
+        );
+        describe_gauge!(
+            "blockspeed_rolling_mgas",
+            "million gas per second over the rolling window"
+        );
+        describe_gauge!(
+            "blockspeed_rolling_tps",
+            "transactions per second over the rolling window"
+        );
+        describe_gauge!(
+            "blockspeed_block_gas_limit",
+            Unit::Count,
+            "gas limit of the last block"
+        );
+        describe_gauge!(
+            "blockspeed_block_gas_utilisation",
+            "gas used over gas limit of the last block"
+        );
+        describe_gauge!(
+            "blockspeed_base_fee_gwei",
+            "base fee of the last block in gwei"
+        );
+        describe_gauge!(
+            "blockspeed_blob_gas_used",
+            Unit::Count,
+            "blob gas used by the last block"
+        );
+        describe_gauge!(
+            "blockspeed_blob_base_fee_gwei",
+            "blob base fee of the last block in gwei"
+        );
+        describe_gauge!(
+            "blockspeed_block_system_txs",
+            Unit::Count,
+            "L2 system and deposit transactions in the last block"
+        );
+        describe_gauge!(
+            "blockspeed_l1_origin_block",
+            "L1 block number the last L2 block was derived from"
+        );
+        describe_gauge!(
+            "blockspeed_mempool_inflow_tps",
+            "pending transactions first seen per second up to the last block"
+        );
+        describe_gauge!(
+            "blockspeed_mempool_pending",
+            Unit::Count,
+            "pending transactions seen but not yet included"
+        );
+        describe_histogram!(
+            "blockspeed_inclusion_latency",
+            Unit::Seconds,
+            "time from first seeing a transaction pending to its block arriving"
+        );
+        describe_counter!(
+            "blockspeed_builder_blocks",
+            Unit::Count,
+            "blocks by builder tag"
+        );
+        describe_counter!(
+            "blockspeed_builder_gas_used",
+            Unit::Count,
+            "gas used by builder tag"
+        );
+        describe_counter!(
+            "blockspeed_builder_txs",
+            Unit::Count,
+            "transactions by builder tag"
+        );
+        describe_histogram!("blockspeed_gas_used", Unit::Count, "gas used per block");
+        describe_histogram!("blockspeed_txs", Unit::Count, "transactions per block");
+        describe_histogram!(
+            "blockspeed_mgas_per_block",
+            "million gas per second per block"
+        );
+        describe_histogram!(
+            "blockspeed_tps_per_block",
+            "transactions per second per block"
+        );
+        describe_histogram!(
+            "blockspeed_block_interval",
+            Unit::Seconds,
+            "time between consecutive blocks"
+        );
+        describe_histogram!(
+            "blockspeed_reorg_depth",
+            Unit::Count,
+            "depth of detected reorgs"
+        );
+
+        Self
+    }
+
+    fn record(block: &BlockMetrics) {
+        let endpoint = block.endpoint.clone();
+
+        gauge!("blockspeed_block_number", "endpoint" => endpoint.clone()).set(block.number as f64);
+        gauge!("blockspeed_block_gas_used", "endpoint" => endpoint.clone())
+            .set(block.gas_used as f64);
+        gauge!("blockspeed_block_txs", "endpoint" => endpoint.clone()).set(block.tx_count as f64);
+        histogram!("blockspeed_gas_used", "endpoint" => endpoint.clone())
+            .record(block.gas_used as f64);
+        histogram!("blockspeed_txs", "endpoint" => endpoint.clone()).record(block.tx_count as f64);
+        if block.system_tx_count > 0 {
+            gauge!("blockspeed_block_system_txs", "endpoint" => endpoint.clone())
+                .set(block.system_tx_count as f64);
+        }
+        if let Some(mempool) = &block.mempool {
+            gauge!("blockspeed_mempool_inflow_tps", "endpoint" => endpoint.clone())
+                .set(mempool.inflow_tps);
+            gauge!("blockspeed_mempool_pending", "endpoint" => endpoint.clone())
+                .set(mempool.pending as f64);
+            let inclusion =
+                histogram!("blockspeed_inclusion_latency", "endpoint" => endpoint.clone());
+            for latency in &mempool.inclusion_ms {
+                inclusion.record(latency / 1000.0);
+            }
+        }
+        if let Some(origin) = &block.l1_origin {
+            gauge!("blockspeed_l1_origin_block", "endpoint" => endpoint.clone())
+                .set(origin.number as f64);
+        }
+
+        // counters rather than gauges so rate() gives throughput per builder
+        let builder = block
+            .builder
+            .clone()
+            .unwrap_or_else(|| "unknown".to_string());
+        counter!("blockspeed_builder_blocks", "endpoint" => endpoint.clone(), "builder" => builder.clone())
+            .increment(1);
+        counter!("blockspeed_builder_gas_used", "endpoint" => endpoint.clone(), "builder" => builder.clone())
+            .increment(block.gas_used);
+        counter!("blockspeed_builder_txs", "endpoint" => endpoint.clone(), "builder" => builder)
+            .increment(block.tx_count);
+
+        let fee_market = &block.fee_market;
+        gauge!("blockspeed_block_gas_limit", "endpoint" => endpoint.clone())
+            .set(fee_market.gas_limit as f64);
+        gauge!("blockspeed_block_gas_utilisation", "endpoint" => endpoint.clone())
+            .set(fee_market.gas_utilisation);
+        if let Some(base_fee) = fee_market.base_fee_gwei {
+            gauge!("blockspeed_base_fee_gwei", "endpoint" => endpoint.clone()).set(base_fee);
+        }
+        if let Some(blob_gas_used) = fee_market.blob_gas_used {
+            gauge!("blockspeed_blob_gas_used", "endpoint" => endpoint.clone())
+                .set(blob_gas_used as f64);
+        }
+        if let Some(blob_base_fee) = fee_market.blob_base_fee_gwei {
+            gauge!("blockspeed_blob_base_fee_gwei", "endpoint" => endpoint.clone())
+                .set(blob_base_fee);
+        }
+
+        if let Some(rates) = &block.rates {
+            gauge!("blockspeed_mgas", "endpoint" => endpoint.clone()).set(rates.mgas);
+            gauge!("blockspeed_tps", "endpoint" => endpoint.clone()).set(rates.tps);
+            histogram!("blockspeed_mgas_per_block", "endpoint" => endpoint.clone())
+                .record(rates.mgas);
+            histogram!("blockspeed_tps_per_block", "endpoint" => endpoint.clone())
+                .record(rates.tps);
+            histogram!("blockspeed_block_interval", "endpoint" => endpoint.clone())
+                .record(rates.block_time);
+        }
+
+        for (window, rates) in &block.rolling {
+            gauge!(
+                "blockspeed_rolling_mgas",
+                "endpoint" => endpoint.clone(),
+                "window" => window.clone()
+            )
+            .set(rates.mgas);
+            gauge!(
+                "blockspeed_rolling_tps",
+                "endpoint" => endpoint.clone(),
+                "window" => window.clone()
+            )
+            .set(rates.tps);
+        }
+    }
+}
+
+impl Default for PrometheusSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetricsSink for PrometheusSink {
+    fn write_blocks<'a>(&'a self, blocks: &'a [BlockMetrics]) -> BoxFuture<'a, eyre::Result<()>> {
+        blocks.iter().for_each(Self::record);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn write_reorg<'a>(
+        &'a self,
+        endpoint: &'a str,
+        reorg: &'a Reorg,
+        _timestamp_nanos: u64,
+    ) -> BoxFuture<'a, eyre::Result<()>> {
+        histogram!("blockspeed_reorg_depth", "endpoint" => endpoint.to_string())
+            .record(reorg.depth() as f64);
+        Box::pin(async { Ok(()) })
+    }
+}
@@ -0,0 +1,64 @@
+#![allow(missing_docs)]
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// How many block numbers behind the highest announced block are still tracked.
+const TRACKED_BLOCKS: u64 = 128;
+
+#[derive(Debug)]
+struct FirstSeen {
+    number: u64,
+    seen_nanos: u64,
+    endpoint: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delay {
+    /// endpoint that announced the block first
+    pub first_endpoint: String,
+    /// time since the first announcement, `0` for the first announcer itself
+    pub delay_nanos: u64,
+}
+
+/// Records when each endpoint first announces a block hash, shared by all endpoints.
+#[derive(Debug, Default)]
+pub struct PropagationTracker {
+    blocks: Mutex<HashMap<String, FirstSeen>>,
+}
+
+impl PropagationTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the delay of `endpoint` relative to the earliest announcer of `block_hash`.
+    pub fn announce(
+        &self,
+        block_hash: &str,
+        number: u64,
+        endpoint: &str,
+        seen_nanos: u64,
+    ) -> Delay {
+        let mut blocks = self.blocks.lock().unwrap();
+
+        let first = blocks
+            .entry(block_hash.to_string())
+            .or_insert_with(|| FirstSeen {
+                number,
+                seen_nanos,
+                endpoint: endpoint.to_string(),
+            });
+        let delay = Delay {
+            first_endpoint: first.endpoint.clone(),
+            delay_nanos: seen_nanos.saturating_sub(first.seen_nanos),
+        };
+
+        if blocks.len() as u64 > TRACKED_BLOCKS * 2 {
+            let highest = blocks.values().map(|b| b.number).max().unwrap_or_default();
+            blocks.retain(|_, b| b.number + TRACKED_BLOCKS > highest);
+        }
+
+        delay
+    }
+}
@@ -0,0 +1,101 @@
+#![allow(missing_docs)]
+
+use std::time::Duration;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// What an endpoint does when its WebSocket can't be (re)established. Neither policy gives up,
+/// a failing endpoint never takes the process down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReconnectPolicy {
+    /// retry forever with exponential backoff capped at `max_backoff`, plus jitter
+    Unlimited { max_backoff: Duration },
+    /// like `Unlimited`, but after `failures` consecutive failures the endpoint is marked down
+    /// and left alone for `cooldown`. A single failure after the cooldown opens it again.
+    CircuitBreaker {
+        max_backoff: Duration,
+        failures: u32,
+        cooldown: Duration,
+    },
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self::Unlimited {
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retry {
+    pub delay: Duration,
+    /// the circuit breaker tripped, the endpoint should be reported as down
+    pub open: bool,
+}
+
+/// Consecutive failure bookkeeping for one endpoint.
+#[derive(Debug)]
+pub struct Backoff {
+    policy: ReconnectPolicy,
+    delay: Duration,
+    failures: u32,
+    half_open: bool,
+}
+
+impl Backoff {
+    pub fn new(policy: ReconnectPolicy) -> Self {
+        Self {
+            policy,
+            delay: INITIAL_BACKOFF,
+            failures: 0,
+            half_open: false,
+        }
+    }
+
+    /// Consecutive failures since the last success.
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    pub fn reset(&mut self) {
+        self.delay = INITIAL_BACKOFF;
+        self.failures = 0;
+        self.half_open = false;
+    }
+
+    /// Records a failure and returns how long to wait before the next attempt.
+    pub fn fail(&mut self) -> Retry {
+        self.failures += 1;
+
+        let max_backoff = match self.policy {
+            ReconnectPolicy::Unlimited { max_backoff } => max_backoff,
+            ReconnectPolicy::CircuitBreaker {
+                max_backoff,
+                failures,
+                cooldown,
+            } => {
+                if self.half_open || self.failures >= failures {
+                    self.half_open = true;
+                    self.delay = INITIAL_BACKOFF;
+                    return Retry {
+                        delay: cooldown,
+                        open: true,
+                    };
+                }
+                max_backoff
+            }
+        };
+
+        let delay = jitter(self.delay);
+        self.delay = (self.delay * 2).min(max_backoff);
+        Retry { delay, open: false }
+    }
+}
+
+/// Somewhere between half and all of `delay`, so endpoints that failed together don't retry in
+/// lockstep.
+fn jitter(delay: Duration) -> Duration {
+    let half = delay / 2;
+    half + half.mul_f64(fastrand::f64())
+}
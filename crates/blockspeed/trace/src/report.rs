@@ -0,0 +1,289 @@
+#![allow(missing_docs)]
+
+use crate::block::percentile;
+use crate::sink::{BlockMetrics, MetricsSink};
+use futures_util::future::BoxFuture;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Distribution of one value over a run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Summary {
+    pub count: usize,
+    pub min: f64,
+    pub avg: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl Summary {
+    /// Non-finite values (e.g. a rate over a zero gap) are left out, `None` when nothing is left.
+    pub fn new(values: &[f64]) -> Option<Self> {
+        let mut sorted: Vec<f64> = values.iter().copied().filter(|v| v.is_finite()).collect();
+        if sorted.is_empty() {
+            return None;
+        }
+        sorted.sort_unstable_by(f64::total_cmp);
+
+        Some(Self {
+            count: sorted.len(),
+            min: sorted[0],
+            avg: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            p50: percentile(&sorted, 50.0),
+            p95: percentile(&sorted, 95.0),
+            p99: percentile(&sorted, 99.0),
+            max: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointReport {
+    pub blocks: u64,
+    pub first_block: u64,
+    pub last_block: u64,
+    /// seconds
+    pub block_time: Option<Summary>,
+    pub mgas: Option<Summary>,
+    pub tps: Option<Summary>,
+    /// gas used over gas limit
+    pub gas_fill: Option<Summary>,
+    /// milliseconds from first seeing a transaction pending to its block, over all of them
+    pub inclusion_latency: Option<Summary>,
+    /// pending transactions first seen per second, per block
+    pub mempool_inflow: Option<Summary>,
+}
+
+/// End-of-run summary of every endpoint.
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub duration_secs: f64,
+    pub endpoints: BTreeMap<String, EndpointReport>,
+}
+
+impl Report {
+    pub fn to_json(&self) -> eyre::Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| eyre::eyre!("failed to encode report: {}", e))
+    }
+
+    pub fn write_json(&self, path: &str) -> eyre::Result<()> {
+        std::fs::write(path, self.to_json()?)
+            .map_err(|e| eyre::eyre!("failed to write report {}: {}", path, e))
+    }
+
+    pub fn to_table(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "blockspeed report, {:.0}s", self.duration_secs);
+
+        for (name, endpoint) in &self.endpoints {
+            let _ = writeln!(
+                out,
+                "\n{}: {} blocks ({}..={})",
+                name, endpoint.blocks, endpoint.first_block, endpoint.last_block
+            );
+            let _ = writeln!(
+                out,
+                "{:<14} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+                "", "min", "avg", "p50", "p95", "p99", "max"
+            );
+            let mut rows = vec![
+                ("block time s", endpoint.block_time, 1.0),
+                ("mgas/s", endpoint.mgas, 1.0),
+                ("tps", endpoint.tps, 1.0),
+                ("gas fill %", endpoint.gas_fill, 100.0),
+            ];
+            // only with pending transactions tracked
+            if endpoint.mempool_inflow.is_some() {
+                rows.push(("inclusion ms", endpoint.inclusion_latency, 1.0));
+                rows.push(("inflow tx/s", endpoint.mempool_inflow, 1.0));
+            }
+            for (label, summary, scale) in rows {
+                match summary {
+                    Some(s) => {
+                        let _ = writeln!(
+                            out,
+                            "{:<14} {:>10.2} {:>10.2} {:>10.2} {:>10.2} {:>10.2} {:>10.2}",
+                            label,
+                            s.min * scale,
+                            s.avg * scale,
+                            s.p50 * scale,
+                            s.p95 * scale,
+                            s.p99 * scale,
+                            s.max * scale
+                        );
+                    }
+                    None => {
+                        let _ = writeln!(out, "{:<14} {:>10}", label, "-");
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+#[derive(Debug, Default)]
+struct Samples {
+    blocks: u64,
+    first_block: u64,
+    last_block: u64,
+    block_time: Vec<f64>,
+    mgas: Vec<f64>,
+    tps: Vec<f64>,
+    gas_fill: Vec<f64>,
+    inclusion_latency: Vec<f64>,
+    mempool_inflow: Vec<f64>,
+}
+
+/// Keeps the per-block values of a run in memory to summarise them once it ends. Blocks
+/// without per-block rates only count towards gas fill.
+#[derive(Debug)]
+pub struct ReportSink {
+    started: Instant,
+    endpoints: Mutex<BTreeMap<String, Samples>>,
+}
+
+impl Default for ReportSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReportSink {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            endpoints: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn report(&self) -> Report {
+        let endpoints = self
+            .endpoints
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, samples)| {
+                let report = EndpointReport {
+                    blocks: samples.blocks,
+                    first_block: samples.first_block,
+                    last_block: samples.last_block,
+                    block_time: Summary::new(&samples.block_time),
+                    mgas: Summary::new(&samples.mgas),
+                    tps: Summary::new(&samples.tps),
+                    gas_fill: Summary::new(&samples.gas_fill),
+                    inclusion_latency: Summary::new(&samples.inclusion_latency),
+                    mempool_inflow: Summary::new(&samples.mempool_inflow),
+                };
+                (name.clone(), report)
+            })
+            .collect();
+
+        Report {
+            duration_secs: self.started.elapsed().as_secs_f64(),
+            endpoints,
+        }
+    }
+}
+
+impl MetricsSink for ReportSink {
+    fn write_blocks<'a>(&'a self, blocks: &'a [BlockMetrics]) -> BoxFuture<'a, eyre::Result<()>> {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        for block in blocks {
+            let samples = endpoints.entry(block.endpoint.clone()).or_default();
+            if samples.blocks == 0 {
+                samples.first_block = block.number;
+            }
+            samples.blocks += 1;
+            samples.first_block = samples.first_block.min(block.number);
+            samples.last_block = samples.last_block.max(block.number);
+            samples.gas_fill.push(block.fee_market.gas_utilisation);
+            if let Some(mempool) = &block.mempool {
+                samples
+                    .inclusion_latency
+                    .extend_from_slice(&mempool.inclusion_ms);
+                samples.mempool_inflow.push(mempool.inflow_tps);
+            }
+            if let Some(rates) = &block.rates {
+                samples.block_time.push(rates.block_time);
+                samples.mgas.push(rates.mgas);
+                samples.tps.push(rates.tps);
+            }
+        }
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::throughput::Rates;
+
+    #[test]
+    fn percentile_is_nearest_rank() {
+        let sorted: Vec<f64> = (1..=10).map(f64::from).collect();
+        assert_eq!(percentile(&sorted, 0.0), 1.0);
+        assert_eq!(percentile(&sorted, 50.0), 5.0);
+        assert_eq!(percentile(&sorted, 95.0), 10.0);
+        assert_eq!(percentile(&sorted, 100.0), 10.0);
+        assert_eq!(percentile(&[7.0], 99.0), 7.0);
+        assert_eq!(percentile::<f64>(&[], 50.0), 0.0);
+    }
+
+    #[test]
+    fn summary_of_nothing_is_none() {
+        assert_eq!(Summary::new(&[]), None);
+        assert_eq!(Summary::new(&[f64::NAN, f64::INFINITY]), None);
+    }
+
+    #[test]
+    fn summary_sorts_and_skips_non_finite_values() {
+        let summary = Summary::new(&[4.0, f64::INFINITY, 1.0, 3.0, 2.0]).unwrap();
+        assert_eq!(
+            summary,
+            Summary {
+                count: 4,
+                min: 1.0,
+                avg: 2.5,
+                p50: 2.0,
+                p95: 4.0,
+                p99: 4.0,
+                max: 4.0,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn report_counts_every_block_but_only_rates_it_has() {
+        let sink = ReportSink::new();
+        let block = |number: u64, rates: Option<Rates>| BlockMetrics {
+            endpoint: "local".to_string(),
+            number,
+            rates,
+            ..Default::default()
+        };
+        let rates = Rates {
+            mgas: 2.0,
+            tps: 20.0,
+            block_time: 12.0,
+        };
+        sink.write_blocks(&[block(11, Some(rates)), block(10, None)])
+            .await
+            .unwrap();
+
+        let report = sink.report();
+        let endpoint = &report.endpoints["local"];
+        assert_eq!(endpoint.blocks, 2);
+        assert_eq!((endpoint.first_block, endpoint.last_block), (10, 11));
+        assert_eq!(endpoint.block_time.map(|s| s.count), Some(1));
+        assert_eq!(endpoint.gas_fill.map(|s| s.count), Some(2));
+        assert_eq!(endpoint.inclusion_latency, None);
+        assert!(report.to_table().contains("local: 2 blocks (10..=11)"));
+    }
+}
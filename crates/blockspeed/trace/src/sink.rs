@@ -0,0 +1,148 @@
+#![allow(missing_docs)]
+
+use crate::block::{BlockStats, FeeMarket};
+use crate::chain::Reorg;
+use crate::l2::L1Origin;
+use crate::mempool::MempoolStats;
+use crate::propagation::Delay;
+use crate::throughput::Rates;
+use futures_util::future::{join_all, BoxFuture};
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// The sink backends selectable from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SinkKind {
+    InfluxDb,
+    Prometheus,
+    /// raw line protocol over HTTP (InfluxDB 1.x `/write`) or UDP
+    LineProtocol,
+    /// per-block CSV or JSON lines files
+    File,
+}
+
+impl FromStr for SinkKind {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "influxdb" => Ok(Self::InfluxDb),
+            "prometheus" => Ok(Self::Prometheus),
+            "line-protocol" => Ok(Self::LineProtocol),
+            "file" => Ok(Self::File),
+            _ => Err(eyre::eyre!("invalid sink: {}", s)),
+        }
+    }
+}
+
+/// Everything blockspeed computed for a single block on a single endpoint.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockMetrics {
+    pub endpoint: String,
+    pub number: u64,
+    pub hash: String,
+    pub block_timestamp: u64,
+    /// arrival time for live blocks, block time for backfilled ones
+    pub timestamp_nanos: u64,
+    pub backfilled: bool,
+    pub gas_used: u64,
+    /// user transactions, system and deposit transactions of L2s are excluded
+    pub tx_count: u64,
+    /// L2 system and deposit transactions, always 0 on L1
+    pub system_tx_count: u64,
+    /// L1 block an L2 block was derived from, when the chain exposes it
+    pub l1_origin: Option<L1Origin>,
+    /// `miner` of the header, lowercase hex
+    pub fee_recipient: String,
+    /// decoded `extraData`, see [`builder_tag`](crate::block::builder_tag)
+    pub builder: Option<String>,
+    /// per-block rates, `None` when there was no previous block to measure against
+    pub rates: Option<Rates>,
+    /// rolling rates by window label
+    pub rolling: Vec<(String, Rates)>,
+    pub propagation: Option<Delay>,
+    /// only for live blocks with `pending_txs` enabled
+    pub mempool: Option<MempoolStats>,
+    pub fee_market: FeeMarket,
+    pub stats: Option<BlockStats>,
+}
+
+/// A destination for block metrics. The InfluxDB [`Output`](crate::output::Output) is one
+/// backend, [`MultiSink`] writes to several at once.
+pub trait MetricsSink: Debug + Send + Sync {
+    fn write_blocks<'a>(&'a self, blocks: &'a [BlockMetrics]) -> BoxFuture<'a, eyre::Result<()>>;
+
+    fn write_reorg<'a>(
+        &'a self,
+        _endpoint: &'a str,
+        _reorg: &'a Reorg,
+        _timestamp_nanos: u64,
+    ) -> BoxFuture<'a, eyre::Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Waits until everything written so far has reached the backend, sinks that write
+    /// immediately have nothing to do.
+    fn flush(&self) -> BoxFuture<'_, eyre::Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Fans every write out to all inner sinks, a failing sink doesn't stop the others.
+#[derive(Debug, Default)]
+pub struct MultiSink {
+    sinks: Vec<Arc<dyn MetricsSink>>,
+}
+
+impl MultiSink {
+    pub fn new(sinks: Vec<Arc<dyn MetricsSink>>) -> Self {
+        Self { sinks }
+    }
+
+    fn collect_errors(results: Vec<eyre::Result<()>>) -> eyre::Result<()> {
+        let errors: Vec<String> = results
+            .into_iter()
+            .filter_map(|res| res.err().map(|e| e.to_string()))
+            .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(eyre::eyre!(errors.join("; ")))
+        }
+    }
+}
+
+impl MetricsSink for MultiSink {
+    fn write_blocks<'a>(&'a self, blocks: &'a [BlockMetrics]) -> BoxFuture<'a, eyre::Result<()>> {
+        Box::pin(async move {
+            let results = join_all(self.sinks.iter().map(|sink| sink.write_blocks(blocks))).await;
+            Self::collect_errors(results)
+        })
+    }
+
+    fn write_reorg<'a>(
+        &'a self,
+        endpoint: &'a str,
+        reorg: &'a Reorg,
+        timestamp_nanos: u64,
+    ) -> BoxFuture<'a, eyre::Result<()>> {
+        Box::pin(async move {
+            let results = join_all(
+                self.sinks
+                    .iter()
+                    .map(|sink| sink.write_reorg(endpoint, reorg, timestamp_nanos)),
+            )
+            .await;
+            Self::collect_errors(results)
+        })
+    }
+
+    fn flush(&self) -> BoxFuture<'_, eyre::Result<()>> {
+        Box::pin(async move {
+            let results = join_all(self.sinks.iter().map(|sink| sink.flush())).await;
+            Self::collect_errors(results)
+        })
+    }
+}
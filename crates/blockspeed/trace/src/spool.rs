@@ -0,0 +1,153 @@
+#![allow(missing_docs)]
+
+use metrics::{counter, describe_counter, describe_gauge, gauge};
+use std::path::{Path, PathBuf};
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+/// Line-protocol file holding points that couldn't be written while the sink was unreachable.
+/// Lines are appended in write order and replayed oldest first; once the file reaches
+/// `max_bytes` further points are dropped instead of growing it.
+#[derive(Debug)]
+pub struct Spool {
+    path: PathBuf,
+    max_bytes: u64,
+    bytes: u64,
+}
+
+impl Spool {
+    /// Opens the spool at `path`, picking up whatever a previous run left behind.
+    pub fn open(path: impl AsRef<Path>, max_bytes: u64) -> eyre::Result<Self> {
+        describe_gauge!("blockspeed_spool_bytes", "Bytes waiting in the spool file");
+        describe_counter!(
+            "blockspeed_spool_points",
+            "Points written to the spool file"
+        );
+        describe_counter!(
+            "blockspeed_spool_replayed",
+            "Points replayed from the spool file"
+        );
+        describe_counter!(
+            "blockspeed_spool_dropped",
+            "Points dropped because the spool file was full"
+        );
+        describe_counter!(
+            "blockspeed_spool_rejected",
+            "Spooled points InfluxDB rejected on replay"
+        );
+
+        let path = path.as_ref().to_path_buf();
+        let bytes = match std::fs::metadata(&path) {
+            Ok(meta) => meta.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => {
+                return Err(eyre::eyre!(
+                    "failed to open spool {}: {}",
+                    path.display(),
+                    e
+                ))
+            }
+        };
+        if bytes > 0 {
+            info!(
+                "found {} bytes of spooled points in {}",
+                bytes,
+                path.display()
+            );
+        }
+        gauge!("blockspeed_spool_bytes").set(bytes as f64);
+
+        Ok(Self {
+            path,
+            max_bytes,
+            bytes,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes == 0
+    }
+
+    /// Appends line protocol, one point per line. Returns how many points were dropped because
+    /// the spool is full.
+    pub async fn append(&mut self, lines: &str) -> eyre::Result<usize> {
+        let mut kept = String::with_capacity(lines.len());
+        let mut spooled = 0;
+        let mut dropped = 0;
+        for line in lines.lines().filter(|l| !l.is_empty()) {
+            if self.bytes + (kept.len() + line.len() + 1) as u64 > self.max_bytes {
+                dropped += 1;
+                continue;
+            }
+            kept.push_str(line);
+            kept.push('\n');
+            spooled += 1;
+        }
+
+        if !kept.is_empty() {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await
+                .map_err(|e| eyre::eyre!("failed to open spool {}: {}", self.path.display(), e))?;
+            file.write_all(kept.as_bytes()).await?;
+            file.flush().await?;
+            self.bytes += kept.len() as u64;
+        }
+
+        counter!("blockspeed_spool_points").increment(spooled);
+        if dropped > 0 {
+            counter!("blockspeed_spool_dropped").increment(dropped as u64);
+            warn!(
+                "spool {} is full, dropped {} points",
+                self.path.display(),
+                dropped
+            );
+        }
+        gauge!("blockspeed_spool_bytes").set(self.bytes as f64);
+
+        Ok(dropped)
+    }
+
+    /// Reads back every spooled line in order.
+    pub async fn read(&self) -> eyre::Result<Vec<String>> {
+        if self.is_empty() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&self.path)
+            .await
+            .map_err(|e| eyre::eyre!("failed to read spool {}: {}", self.path.display(), e))?;
+        Ok(content
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Replaces the spool with the lines that are still pending after a partial replay.
+    pub async fn retain(&mut self, remaining: &[String], replayed: usize) -> eyre::Result<()> {
+        counter!("blockspeed_spool_replayed").increment(replayed as u64);
+
+        if remaining.is_empty() {
+            match fs::remove_file(&self.path).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+            self.bytes = 0;
+        } else {
+            let mut content = remaining.join("\n");
+            content.push('\n');
+            // write to a sibling file first so a crash mid-write can't lose the spool
+            let tmp = self.path.with_extension("tmp");
+            fs::write(&tmp, &content).await?;
+            fs::rename(&tmp, &self.path).await?;
+            self.bytes = content.len() as u64;
+        }
+        gauge!("blockspeed_spool_bytes").set(self.bytes as f64);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,279 @@
+#![allow(missing_docs)]
+
+use metrics::{counter, describe_counter, describe_gauge, gauge};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+/// Sink writes older than this no longer count towards the error rate.
+const SINK_ERROR_WINDOW: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    Connecting,
+    Subscribed,
+    Polling,
+    Reconnecting,
+    /// the circuit breaker is open
+    Down,
+    Stopped,
+}
+
+impl ConnectionState {
+    pub const ALL: [Self; 6] = [
+        Self::Connecting,
+        Self::Subscribed,
+        Self::Polling,
+        Self::Reconnecting,
+        Self::Down,
+        Self::Stopped,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Connecting => "connecting",
+            Self::Subscribed => "subscribed",
+            Self::Polling => "polling",
+            Self::Reconnecting => "reconnecting",
+            Self::Down => "down",
+            Self::Stopped => "stopped",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointStatus {
+    pub state: ConnectionState,
+    /// unix milliseconds of the last state change
+    pub since_ms: u64,
+    pub transitions: u64,
+    pub last_error: Option<String>,
+    /// unix milliseconds of the last new head
+    pub last_head_ms: Option<u64>,
+}
+
+/// When `/health` stops reporting ready.
+#[derive(Debug, Clone)]
+pub struct HealthThresholds {
+    /// longest an endpoint may go without a new head, counted from its first state change
+    /// until the first head arrives
+    pub max_head_age: Duration,
+    /// highest share of failed writes per sink over the last five minutes
+    pub max_sink_error_rate: f64,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        Self {
+            max_head_age: Duration::from_secs(120),
+            max_sink_error_rate: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointHealth {
+    #[serde(flatten)]
+    pub status: EndpointStatus,
+    pub head_age_ms: u64,
+    pub healthy: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SinkHealth {
+    pub writes: u64,
+    pub errors: u64,
+    pub error_rate: f64,
+    pub healthy: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub healthy: bool,
+    pub endpoints: BTreeMap<String, EndpointHealth>,
+    pub sinks: BTreeMap<String, SinkHealth>,
+    /// why the report is unhealthy, empty when it isn't
+    pub problems: Vec<String>,
+}
+
+/// Connection state of every endpoint and recent sink write outcomes, shared between the
+/// subscriptions, the buffered sinks and the health endpoint. Every transition is also
+/// exported as metrics.
+#[derive(Debug, Default)]
+pub struct StatusRegistry {
+    endpoints: RwLock<BTreeMap<String, EndpointStatus>>,
+    sinks: Mutex<BTreeMap<String, VecDeque<(Instant, bool)>>>,
+}
+
+impl StatusRegistry {
+    pub fn new() -> Self {
+        describe_gauge!(
+            "blockspeed_connection_state",
+            "1 for the current connection state of an endpoint, 0 for the others"
+        );
+        describe_counter!(
+            "blockspeed_connection_transitions",
+            "Connection state changes by endpoint and new state"
+        );
+        Self::default()
+    }
+
+    pub fn set_state(&self, endpoint: &str, state: ConnectionState, error: Option<String>) {
+        let mut endpoints = self.endpoints.write().unwrap();
+        let previous = endpoints.get(endpoint).map(|status| status.state);
+
+        let status = endpoints
+            .entry(endpoint.to_string())
+            .or_insert_with(|| EndpointStatus {
+                state,
+                since_ms: now_ms(),
+                transitions: 0,
+                last_error: None,
+                last_head_ms: None,
+            });
+        if error.is_some() {
+            status.last_error = error;
+        }
+        if previous == Some(state) {
+            return;
+        }
+
+        status.state = state;
+        status.since_ms = now_ms();
+        status.transitions += 1;
+        info!(
+            "endpoint {} is {} (was {})",
+            endpoint,
+            state.as_str(),
+            previous.map_or("unknown", |s| s.as_str())
+        );
+
+        counter!(
+            "blockspeed_connection_transitions",
+            "endpoint" => endpoint.to_string(),
+            "state" => state.as_str()
+        )
+        .increment(1);
+        for s in ConnectionState::ALL {
+            gauge!(
+                "blockspeed_connection_state",
+                "endpoint" => endpoint.to_string(),
+                "state" => s.as_str()
+            )
+            .set(if s == state { 1.0 } else { 0.0 });
+        }
+    }
+
+    pub fn record_head(&self, endpoint: &str) {
+        if let Some(status) = self.endpoints.write().unwrap().get_mut(endpoint) {
+            status.last_head_ms = Some(now_ms());
+        }
+    }
+
+    pub fn record_write(&self, sink: &str, ok: bool) {
+        let mut sinks = self.sinks.lock().unwrap();
+        let writes = sinks.entry(sink.to_string()).or_default();
+        writes.push_back((Instant::now(), ok));
+        prune(writes);
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, EndpointStatus> {
+        self.endpoints.read().unwrap().clone()
+    }
+
+    pub fn report(&self, thresholds: &HealthThresholds) -> HealthReport {
+        let now = now_ms();
+        let mut problems = Vec::new();
+
+        let endpoints = self
+            .snapshot()
+            .into_iter()
+            .map(|(name, status)| {
+                let head_age_ms =
+                    now.saturating_sub(status.last_head_ms.unwrap_or(status.since_ms));
+                let mut healthy = true;
+                if head_age_ms > thresholds.max_head_age.as_millis() as u64 {
+                    healthy = false;
+                    problems.push(format!(
+                        "{} has had no new head for {}s",
+                        name,
+                        head_age_ms / 1000
+                    ));
+                }
+                if matches!(
+                    status.state,
+                    ConnectionState::Down | ConnectionState::Stopped
+                ) {
+                    healthy = false;
+                    problems.push(format!("{} is {}", name, status.state.as_str()));
+                }
+                let health = EndpointHealth {
+                    status,
+                    head_age_ms,
+                    healthy,
+                };
+                (name, health)
+            })
+            .collect();
+
+        let sinks = self
+            .sinks
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .map(|(name, writes)| {
+                prune(writes);
+                let errors = writes.iter().filter(|(_, ok)| !ok).count() as u64;
+                let total = writes.len() as u64;
+                let error_rate = if total == 0 {
+                    0.0
+                } else {
+                    errors as f64 / total as f64
+                };
+                let healthy = error_rate <= thresholds.max_sink_error_rate;
+                if !healthy {
+                    problems.push(format!(
+                        "{} failed {} of {} writes in the last {}s",
+                        name,
+                        errors,
+                        total,
+                        SINK_ERROR_WINDOW.as_secs()
+                    ));
+                }
+                let health = SinkHealth {
+                    writes: total,
+                    errors,
+                    error_rate,
+                    healthy,
+                };
+                (name.clone(), health)
+            })
+            .collect();
+
+        HealthReport {
+            healthy: problems.is_empty(),
+            endpoints,
+            sinks,
+            problems,
+        }
+    }
+}
+
+fn prune(writes: &mut VecDeque<(Instant, bool)>) {
+    while writes
+        .front()
+        .is_some_and(|(at, _)| at.elapsed() > SINK_ERROR_WINDOW)
+    {
+        writes.pop_front();
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
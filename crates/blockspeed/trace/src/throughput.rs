@@ -0,0 +1,457 @@
+#![allow(missing_docs)]
+
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+/// Which clock per-block throughput is measured against.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ThroughputClock {
+    /// local wall-clock gap between `newHeads` notifications
+    #[default]
+    Arrival,
+    /// header timestamps, spread over the block number gap
+    Block,
+}
+
+impl FromStr for ThroughputClock {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "arrival" => Ok(Self::Arrival),
+            "block" => Ok(Self::Block),
+            _ => Err(eyre::eyre!("invalid throughput clock: {}", s)),
+        }
+    }
+}
+
+/// Parses a window such as `30s`, `5m` or `1h` into seconds.
+pub fn parse_window(s: &str) -> eyre::Result<u64> {
+    let s = s.trim();
+    let (value, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let value: u64 = value
+        .parse()
+        .map_err(|e| eyre::eyre!("failed to parse window '{}': {}", s, e))?;
+    let secs = match unit {
+        "" | "s" => value,
+        "m" => value * 60,
+        "h" => value * 3600,
+        _ => return Err(eyre::eyre!("invalid window unit in '{}'", s)),
+    };
+    if secs == 0 {
+        return Err(eyre::eyre!("window must not be zero: {}", s));
+    }
+    Ok(secs)
+}
+
+/// Formats a window in seconds back into its shortest label, used as the `window` tag.
+pub fn window_label(secs: u64) -> String {
+    if secs.is_multiple_of(3600) {
+        format!("{}h", secs / 3600)
+    } else if secs.is_multiple_of(60) {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rates {
+    pub mgas: f64,
+    pub tps: f64,
+    pub block_time: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    timestamp: f64,
+    number: u64,
+    gas_used: u64,
+    tx_count: u64,
+}
+
+/// Throughput averaged over all blocks seen within the trailing `secs` seconds.
+#[derive(Debug)]
+pub struct RollingWindow {
+    secs: u64,
+    samples: VecDeque<Sample>,
+}
+
+impl RollingWindow {
+    pub fn new(secs: u64) -> Self {
+        Self {
+            secs,
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        window_label(self.secs)
+    }
+
+    fn push(&mut self, sample: Sample) {
+        // a lower or equal number means a reorg or a replayed block, start over from it
+        if self
+            .samples
+            .back()
+            .is_some_and(|last| sample.number <= last.number)
+        {
+            self.samples.retain(|s| s.number < sample.number);
+        }
+        self.samples.push_back(sample);
+
+        while let Some(first) = self.samples.front() {
+            if sample.timestamp - first.timestamp > self.secs as f64 {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// The oldest sample only marks the start of the window, so its gas and txs are not counted.
+    pub fn rates(&self) -> Option<Rates> {
+        let first = self.samples.front()?;
+        let last = self.samples.back()?;
+
+        let span = last.timestamp - first.timestamp;
+        let blocks = last.number.saturating_sub(first.number);
+        if span <= 0.0 || blocks == 0 {
+            return None;
+        }
+
+        let (gas_used, tx_count) = self
+            .samples
+            .iter()
+            .skip(1)
+            .fold((0u64, 0u64), |(gas, txs), s| {
+                (gas + s.gas_used, txs + s.tx_count)
+            });
+
+        Some(Rates {
+            mgas: gas_used as f64 / span / 1_000_000.0,
+            tps: tx_count as f64 / span,
+            block_time: span / blocks as f64,
+        })
+    }
+}
+
+/// Per-block and rolling throughput for a single endpoint.
+#[derive(Debug)]
+pub struct Throughput {
+    clock: ThroughputClock,
+    sub_second: bool,
+    last_arrival_nanos: Option<u64>,
+    /// blocks were backfilled since the last arrival, which therefore predates the gap
+    backfilled: bool,
+    last_block: Option<(u64, u64)>,
+    windows: Vec<RollingWindow>,
+}
+
+impl Throughput {
+    pub fn new(clock: ThroughputClock, windows: &[u64]) -> Self {
+        Self {
+            clock,
+            sub_second: false,
+            last_arrival_nanos: None,
+            backfilled: false,
+            last_block: None,
+            windows: windows
+                .iter()
+                .map(|secs| RollingWindow::new(*secs))
+                .collect(),
+        }
+    }
+
+    /// Measures blocks that arrive less than a second apart, as on L2s. Arrival rates use the
+    /// real gap, and blocks sharing a header timestamp are timed together once it advances.
+    pub fn with_sub_second_blocks(mut self, sub_second: bool) -> Self {
+        self.sub_second = sub_second;
+        self
+    }
+
+    /// Records a block and returns its per-block rates, or `None` when there is no previous
+    /// block to measure against.
+    pub fn record(
+        &mut self,
+        arrival_nanos: u64,
+        number: u64,
+        block_timestamp: u64,
+        gas_used: u64,
+        tx_count: u64,
+    ) -> Option<Rates> {
+        let prev_arrival_nanos = self.last_arrival_nanos.replace(arrival_nanos);
+        let prev_block = self.advance_block(number, block_timestamp);
+        let backfilled = std::mem::take(&mut self.backfilled);
+
+        self.push_sample(number, block_timestamp, gas_used, tx_count);
+
+        match self.clock {
+            // the backfilled blocks already cover the gap since the previous arrival, measure
+            // the first live block after them against the last backfilled header instead
+            ThroughputClock::Arrival if backfilled => {
+                self.block_rates(prev_block?, number, block_timestamp, gas_used, tx_count)
+            }
+            ThroughputClock::Arrival => {
+                let diff_nanos = arrival_nanos.saturating_sub(prev_arrival_nanos?);
+                let diff_secs = diff_nanos as f64 / 1_000_000_000.0;
+
+                let tps = if self.sub_second {
+                    if diff_nanos == 0 {
+                        return None;
+                    }
+                    tx_count as f64 / diff_secs
+                } else if diff_nanos >= 1_000_000_000 {
+                    tx_count as f64 / diff_secs
+                } else {
+                    tx_count as f64
+                };
+
+                Some(Rates {
+                    mgas: gas_used as f64 / diff_secs / 1_000_000.0,
+                    tps,
+                    block_time: diff_secs,
+                })
+            }
+            ThroughputClock::Block => {
+                self.block_rates(prev_block?, number, block_timestamp, gas_used, tx_count)
+            }
+        }
+    }
+
+    /// Records a block fetched after the fact. Its arrival time means nothing, so the per-block
+    /// rates always come from the header timestamps, and so do those of the next live block.
+    pub fn record_backfilled(
+        &mut self,
+        number: u64,
+        block_timestamp: u64,
+        gas_used: u64,
+        tx_count: u64,
+    ) -> Option<Rates> {
+        let prev_block = self.advance_block(number, block_timestamp);
+        self.backfilled = true;
+
+        self.push_sample(number, block_timestamp, gas_used, tx_count);
+
+        self.block_rates(prev_block?, number, block_timestamp, gas_used, tx_count)
+    }
+
+    /// Moves the reference block forward and returns the previous one. With sub-second blocks
+    /// the reference stays on the first block of a timestamp until the timestamp advances.
+    fn advance_block(&mut self, number: u64, block_timestamp: u64) -> Option<(u64, u64)> {
+        let prev_block = self.last_block;
+        let same_second = prev_block.is_some_and(|(prev_number, prev_timestamp)| {
+            prev_timestamp == block_timestamp && number > prev_number
+        });
+        if !(self.sub_second && same_second) {
+            self.last_block = Some((number, block_timestamp));
+        }
+        prev_block
+    }
+
+    fn block_rates(
+        &self,
+        (prev_number, prev_timestamp): (u64, u64),
+        number: u64,
+        block_timestamp: u64,
+        gas_used: u64,
+        tx_count: u64,
+    ) -> Option<Rates> {
+        if number <= prev_number {
+            return None;
+        }
+
+        // blocks in between (dropped notifications, reconnects, or sub-second blocks sharing
+        // the previous timestamp) share the time span
+        let block_time =
+            block_timestamp.saturating_sub(prev_timestamp) as f64 / (number - prev_number) as f64;
+        let secs = if block_time > 0.0 {
+            block_time
+        } else if self.sub_second {
+            return None;
+        } else {
+            1.0
+        };
+
+        Some(Rates {
+            mgas: gas_used as f64 / secs / 1_000_000.0,
+            tps: tx_count as f64 / secs,
+            block_time,
+        })
+    }
+
+    fn push_sample(&mut self, number: u64, block_timestamp: u64, gas_used: u64, tx_count: u64) {
+        let timestamp = match self.clock {
+            // nothing to place the block at before the first arrival
+            ThroughputClock::Arrival => match self.last_arrival_nanos {
+                Some(nanos) => nanos as f64 / 1_000_000_000.0,
+                None => return,
+            },
+            ThroughputClock::Block => block_timestamp as f64,
+        };
+        let sample = Sample {
+            timestamp,
+            number,
+            gas_used,
+            tx_count,
+        };
+        for window in &mut self.windows {
+            window.push(sample);
+        }
+    }
+
+    /// Rolls back to `number` after a reorg so the re-emitted blocks are measured against the
+    /// common ancestor rather than the orphaned tip.
+    pub fn rewind(&mut self, number: u64, block_timestamp: u64) {
+        self.last_block = Some((number, block_timestamp));
+        for window in &mut self.windows {
+            window.samples.retain(|s| s.number <= number);
+        }
+    }
+
+    pub fn rolling(&self) -> impl Iterator<Item = (String, Rates)> + '_ {
+        self.windows
+            .iter()
+            .filter_map(|window| window.rates().map(|rates| (window.label(), rates)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEC: u64 = 1_000_000_000;
+
+    #[test]
+    fn parses_and_labels_windows() {
+        assert_eq!(parse_window("30s").unwrap(), 30);
+        assert_eq!(parse_window("5m").unwrap(), 300);
+        assert_eq!(parse_window("1h").unwrap(), 3600);
+        assert!(parse_window("0m").is_err());
+        assert!(parse_window("5d").is_err());
+        assert_eq!(window_label(90), "90s");
+        assert_eq!(window_label(300), "5m");
+        assert_eq!(window_label(7200), "2h");
+    }
+
+    #[test]
+    fn empty_window_has_no_rates() {
+        let mut window = RollingWindow::new(60);
+        assert_eq!(window.rates(), None);
+
+        // a single block only marks the start
+        window.push(Sample {
+            timestamp: 100.0,
+            number: 1,
+            gas_used: 1_000_000,
+            tx_count: 10,
+        });
+        assert_eq!(window.rates(), None);
+    }
+
+    #[test]
+    fn window_skips_the_oldest_sample_and_drops_expired_ones() {
+        let mut window = RollingWindow::new(30);
+        for (i, timestamp) in [0.0, 12.0, 24.0, 36.0].into_iter().enumerate() {
+            window.push(Sample {
+                timestamp,
+                number: i as u64,
+                gas_used: 12_000_000,
+                tx_count: 120,
+            });
+        }
+
+        // block 0 fell out of the 30s window, block 1 marks the start
+        let rates = window.rates().unwrap();
+        assert_eq!(rates.block_time, 12.0);
+        assert_eq!(rates.mgas, 1.0);
+        assert_eq!(rates.tps, 10.0);
+    }
+
+    #[test]
+    fn window_starts_over_from_a_lower_number() {
+        let mut window = RollingWindow::new(60);
+        for number in [10, 11, 12] {
+            window.push(Sample {
+                timestamp: number as f64 * 12.0,
+                number,
+                gas_used: 0,
+                tx_count: 0,
+            });
+        }
+        window.push(Sample {
+            timestamp: 150.0,
+            number: 11,
+            gas_used: 0,
+            tx_count: 0,
+        });
+        assert_eq!(window.samples.len(), 2);
+        assert_eq!(window.samples.back().unwrap().number, 11);
+    }
+
+    #[test]
+    fn first_block_has_no_rates() {
+        for clock in [ThroughputClock::Arrival, ThroughputClock::Block] {
+            let mut throughput = Throughput::new(clock, &[60]);
+            assert_eq!(throughput.record(100 * SEC, 1, 100, 30_000_000, 100), None);
+            assert_eq!(throughput.rolling().count(), 0);
+        }
+    }
+
+    #[test]
+    fn arrival_clock_measures_the_arrival_gap() {
+        let mut throughput = Throughput::new(ThroughputClock::Arrival, &[]);
+        throughput.record(100 * SEC, 1, 100, 0, 0);
+        let rates = throughput
+            .record(112 * SEC, 2, 112, 24_000_000, 240)
+            .unwrap();
+        assert_eq!(rates.block_time, 12.0);
+        assert_eq!(rates.mgas, 2.0);
+        assert_eq!(rates.tps, 20.0);
+    }
+
+    #[test]
+    fn block_clock_spreads_the_gap_over_missed_blocks() {
+        let mut throughput = Throughput::new(ThroughputClock::Block, &[]);
+        throughput.record(0, 1, 100, 0, 0);
+        let rates = throughput.record(0, 3, 124, 12_000_000, 120).unwrap();
+        assert_eq!(rates.block_time, 12.0);
+        assert_eq!(rates.mgas, 1.0);
+        assert_eq!(rates.tps, 10.0);
+    }
+
+    #[test]
+    fn live_block_after_a_backfill_uses_the_headers() {
+        let mut throughput = Throughput::new(ThroughputClock::Arrival, &[]);
+        throughput.record(100 * SEC, 1, 100, 0, 0);
+        throughput.record_backfilled(2, 112, 0, 0);
+        // arrived 5 minutes after block 1, but block 2 already covers all but 12s of that
+        let rates = throughput
+            .record(400 * SEC, 3, 124, 12_000_000, 120)
+            .unwrap();
+        assert_eq!(rates.block_time, 12.0);
+        assert_eq!(rates.mgas, 1.0);
+    }
+
+    #[test]
+    fn sub_second_blocks_are_timed_once_the_timestamp_advances() {
+        let mut throughput =
+            Throughput::new(ThroughputClock::Block, &[]).with_sub_second_blocks(true);
+        throughput.record(0, 1, 100, 0, 0);
+        assert_eq!(throughput.record(0, 2, 100, 1_000_000, 10), None);
+        assert_eq!(throughput.record(0, 3, 100, 1_000_000, 10), None);
+        let rates = throughput.record(0, 5, 101, 1_000_000, 10).unwrap();
+        assert_eq!(rates.block_time, 0.25);
+        assert_eq!(rates.tps, 40.0);
+    }
+
+    #[test]
+    fn rewind_measures_reemitted_blocks_against_the_ancestor() {
+        let mut throughput = Throughput::new(ThroughputClock::Block, &[]);
+        throughput.record(0, 1, 100, 0, 0);
+        throughput.record(0, 2, 112, 0, 0);
+        throughput.rewind(1, 100);
+        let rates = throughput.record_backfilled(2, 124, 0, 0).unwrap();
+        assert_eq!(rates.block_time, 24.0);
+    }
+}
@@ -0,0 +1,235 @@
+#![allow(missing_docs)]
+
+use crate::chain::Reorg;
+use crate::sink::{BlockMetrics, MetricsSink};
+use futures_util::future::BoxFuture;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write as _;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+
+/// Blocks kept per endpoint for the sparklines.
+const HISTORY: usize = 60;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Debug, Default)]
+struct EndpointView {
+    number: u64,
+    hash: String,
+    builder: Option<String>,
+    tx_count: u64,
+    mgas: f64,
+    tps: f64,
+    block_time: f64,
+    gas_utilisation: f64,
+    base_fee_gwei: Option<f64>,
+    rolling: Vec<(String, f64, f64)>,
+    backfilled: u64,
+    reorgs: u64,
+    mgas_history: VecDeque<f64>,
+    tps_history: VecDeque<f64>,
+    block_time_history: VecDeque<f64>,
+    txs_history: VecDeque<f64>,
+}
+
+impl EndpointView {
+    fn record(&mut self, block: &BlockMetrics) {
+        self.number = block.number;
+        self.hash = block.hash.clone();
+        self.builder = block.builder.clone();
+        self.tx_count = block.tx_count;
+        self.gas_utilisation = block.fee_market.gas_utilisation;
+        self.base_fee_gwei = block.fee_market.base_fee_gwei;
+        if block.backfilled {
+            self.backfilled += 1;
+        }
+        self.rolling = block
+            .rolling
+            .iter()
+            .map(|(window, rates)| (window.clone(), rates.mgas, rates.tps))
+            .collect();
+
+        if let Some(rates) = &block.rates {
+            self.mgas = rates.mgas;
+            self.tps = rates.tps;
+            self.block_time = rates.block_time;
+            push(&mut self.mgas_history, rates.mgas);
+            push(&mut self.tps_history, rates.tps);
+            push(&mut self.block_time_history, rates.block_time);
+        }
+        push(&mut self.txs_history, block.tx_count as f64);
+    }
+}
+
+fn push(history: &mut VecDeque<f64>, value: f64) {
+    if history.len() == HISTORY {
+        history.pop_front();
+    }
+    history.push_back(value);
+}
+
+/// Renders values scaled between zero and their maximum.
+fn sparkline(values: &VecDeque<f64>) -> String {
+    let max = values.iter().copied().fold(0.0, f64::max);
+    values
+        .iter()
+        .map(|v| {
+            if max <= 0.0 || !v.is_finite() {
+                SPARKS[0]
+            } else {
+                let level = (v / max * (SPARKS.len() - 1) as f64).round() as usize;
+                SPARKS[level.min(SPARKS.len() - 1)]
+            }
+        })
+        .collect()
+}
+
+/// Live terminal view of every endpoint, fed through [`MetricsSink`] like any other sink and
+/// redrawn by [`TuiSink::run`].
+#[derive(Debug, Default)]
+pub struct TuiSink {
+    endpoints: Mutex<BTreeMap<String, EndpointView>>,
+}
+
+impl TuiSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Redraws the view every `interval` until SIGINT or SIGTERM, then restores the terminal so
+    /// whatever is printed on shutdown starts on a clean screen.
+    pub async fn run(&self, interval: Duration) -> eyre::Result<()> {
+        let mut sigint = signal(SignalKind::interrupt())?;
+        let mut sigterm = signal(SignalKind::terminate())?;
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = sigint.recv() => break,
+                _ = sigterm.recv() => break,
+            }
+            let frame = self.render();
+            let mut stdout = std::io::stdout().lock();
+            // clear the screen and move the cursor home before each frame
+            stdout.write_all(b"\x1b[2J\x1b[H")?;
+            stdout.write_all(frame.as_bytes())?;
+            stdout.flush()?;
+        }
+
+        Self::restore()?;
+        Ok(())
+    }
+
+    /// Resets attributes, shows the cursor and leaves an empty screen behind, also needed when
+    /// [`TuiSink::run`] is cancelled rather than stopped by a signal.
+    pub fn restore() -> std::io::Result<()> {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(b"\x1b[0m\x1b[?25h\x1b[2J\x1b[H")?;
+        stdout.flush()
+    }
+
+    fn render(&self) -> String {
+        let endpoints = self.endpoints.lock().unwrap();
+        let mut out = String::new();
+
+        let _ = writeln!(out, "\x1b[1mblockspeed\x1b[0m  (ctrl-c to quit)\n");
+        let _ = writeln!(
+            out,
+            "\x1b[1m{:<16} {:>10} {:>10} {:>10} {:>10} {:>7} {:>6} {:>9} {:>8} {:>7}\x1b[0m",
+            "endpoint",
+            "block",
+            "mgas/s",
+            "tps",
+            "block time",
+            "txs",
+            "fill",
+            "base fee",
+            "backfill",
+            "reorgs"
+        );
+        for (name, view) in endpoints.iter() {
+            let _ = writeln!(
+                out,
+                "{:<16} {:>10} {:>10.2} {:>10.1} {:>9.2}s {:>7} {:>5.1}% {:>9} {:>8} {:>7}",
+                truncate(name, 16),
+                view.number,
+                view.mgas,
+                view.tps,
+                view.block_time,
+                view.tx_count,
+                view.gas_utilisation * 100.0,
+                view.base_fee_gwei
+                    .map_or_else(|| "-".to_string(), |fee| format!("{:.3}", fee)),
+                view.backfilled,
+                view.reorgs
+            );
+        }
+
+        for (name, view) in endpoints.iter() {
+            let _ = writeln!(
+                out,
+                "\n\x1b[1m{}\x1b[0m  {}  {}",
+                name,
+                view.hash,
+                view.builder.as_deref().unwrap_or("-")
+            );
+            let _ = writeln!(out, "  mgas/s     {}", sparkline(&view.mgas_history));
+            let _ = writeln!(out, "  tps        {}", sparkline(&view.tps_history));
+            let _ = writeln!(out, "  block time {}", sparkline(&view.block_time_history));
+            let _ = writeln!(out, "  txs        {}", sparkline(&view.txs_history));
+            for (window, mgas, tps) in &view.rolling {
+                let _ = writeln!(
+                    out,
+                    "  {:<4} avg   {:.2} mgas/s  {:.1} tps",
+                    window, mgas, tps
+                );
+            }
+        }
+
+        if endpoints.is_empty() {
+            let _ = writeln!(out, "waiting for blocks...");
+        }
+        out
+    }
+}
+
+fn truncate(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        s.to_string()
+    } else {
+        s.chars()
+            .take(max - 1)
+            .chain(std::iter::once('…'))
+            .collect()
+    }
+}
+
+impl MetricsSink for TuiSink {
+    fn write_blocks<'a>(&'a self, blocks: &'a [BlockMetrics]) -> BoxFuture<'a, eyre::Result<()>> {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        for block in blocks {
+            endpoints
+                .entry(block.endpoint.clone())
+                .or_default()
+                .record(block);
+        }
+        Box::pin(async { Ok(()) })
+    }
+
+    fn write_reorg<'a>(
+        &'a self,
+        endpoint: &'a str,
+        _reorg: &'a Reorg,
+        _timestamp_nanos: u64,
+    ) -> BoxFuture<'a, eyre::Result<()>> {
+        self.endpoints
+            .lock()
+            .unwrap()
+            .entry(endpoint.to_string())
+            .or_default()
+            .reorgs += 1;
+        Box::pin(async { Ok(()) })
+    }
+}
@@ -1,48 +1,182 @@
 #![allow(missing_docs)]
 
-use crate::output::Output;
-use jsonrpsee::core::client::{ClientT, SubscriptionClientT};
-use jsonrpsee::ws_client::{WsClient, WsClientBuilder};
+use crate::block::{
+    builder_tag, BlockFetch, BlockReceipt, BlockStats, BlockTransaction, FeeMarket, FullBlock,
+};
+use crate::chain::{CanonicalChain, ChainBlock, Reorg, CHAIN_WINDOW};
+use crate::config::{Endpoint, TraceConfig};
+use crate::header::{MalformedHeader, RpcHeader};
+use crate::l2::{Chain, L1Origin};
+use crate::mempool::MempoolTracker;
+use crate::output::block_point_nanos;
+use crate::propagation::PropagationTracker;
+use crate::reconnect::Backoff;
+use crate::sink::{BlockMetrics, MetricsSink};
+use crate::status::{ConnectionState, StatusRegistry};
+use crate::throughput::{Throughput, ThroughputClock};
+use alloy_primitives::{B256, U64};
+use futures_util::{stream, StreamExt};
+use jsonrpsee::core::client::{ClientT, Subscription, SubscriptionClientT};
+use jsonrpsee::core::params::ArrayParams;
+use jsonrpsee::http_client::HttpClientBuilder;
+use jsonrpsee::ws_client::WsClientBuilder;
+use metrics::{counter, describe_counter, describe_histogram, histogram, Unit};
+use serde::de::DeserializeOwned;
 use serde_json::Value;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::signal::unix::{signal, SignalKind};
-use tracing::{error, info};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn, Instrument};
 
-const MAX_RECONNECT_ATTEMPTS: u32 = 10;
+/// Consecutive WebSocket failures before an endpoint with an `http_url` falls back to polling.
+const WS_FAILURES_BEFORE_POLLING: u32 = 3;
+/// How long an endpoint polls before giving its WebSocket another try.
+const POLL_BEFORE_WS_RETRY: Duration = Duration::from_secs(300);
+/// Blocks requested ahead while replaying a range.
+const REPLAY_PREFETCH: usize = 16;
+
+#[derive(Debug, Clone, Copy)]
+enum BlockSource {
+    /// announced through the `newHeads` subscription
+    Live { arrival_nanos: u64 },
+    /// fetched by number to fill a gap in the subscription
+    Backfill,
+    /// fetched by number while replaying a historical range
+    Replay,
+}
+
+/// Per-endpoint state that survives reconnects.
+#[derive(Debug)]
+struct HeadState {
+    throughput: Throughput,
+    chain: CanonicalChain,
+}
 
 #[derive(Debug)]
 pub struct WsConnection {
-    uri: String,
+    endpoint: Endpoint,
+    config: TraceConfig,
+    propagation: Option<Arc<PropagationTracker>>,
+    status: Arc<StatusRegistry>,
+    mempool: Option<Arc<MempoolTracker>>,
 }
 
 impl WsConnection {
-    pub async fn new(uri: &str) -> eyre::Result<Self> {
+    pub async fn new(endpoint: Endpoint, config: TraceConfig) -> eyre::Result<Self> {
+        let mempool = config
+            .pending_txs
+            .then(|| Arc::new(MempoolTracker::new(now_nanos())));
         Ok(Self {
-            uri: uri.to_string(),
+            endpoint,
+            config,
+            propagation: None,
+            status: Arc::new(StatusRegistry::new()),
+            mempool,
         })
     }
 
-    pub async fn subscribe_to_head(&self, output: Arc<Output>) -> eyre::Result<()> {
-        let mut reconnect_attempts = 0;
-        let mut backoff_delay = tokio::time::Duration::from_secs(1);
+    /// Shares a tracker with the other endpoints to record cross-endpoint propagation delay.
+    pub fn with_propagation(mut self, propagation: Arc<PropagationTracker>) -> Self {
+        self.propagation = Some(propagation);
+        self
+    }
+
+    /// Reports connection state changes to a registry shared with the health endpoint.
+    pub fn with_status(mut self, status: Arc<StatusRegistry>) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.endpoint.name
+    }
+
+    pub async fn subscribe_to_head(&self, sink: Arc<dyn MetricsSink>) -> eyre::Result<()> {
+        let mut backoff = Backoff::new(self.config.reconnect);
+
+        describe_counter!(
+            "blockspeed_blocks_skipped",
+            "missed blocks that were not backfilled"
+        );
+        describe_counter!(
+            "blockspeed_blocks_backfilled",
+            "missed blocks fetched by number"
+        );
+        describe_counter!("blockspeed_reorgs", "reorgs detected");
+        describe_counter!(
+            "blockspeed_headers_received",
+            "newHeads notifications and polled heads received"
+        );
+        describe_histogram!(
+            "blockspeed_header_processing_seconds",
+            Unit::Seconds,
+            "time from receiving a head to writing its metrics"
+        );
+        describe_counter!(
+            "blockspeed_rpc_retries",
+            "RPC requests retried because the block wasn't available yet or the call failed"
+        );
+        describe_counter!("blockspeed_reconnects", "WebSocket reconnect attempts");
+        describe_counter!(
+            "blockspeed_malformed_headers",
+            "headers that failed to decode and were skipped"
+        );
+        describe_counter!(
+            "blockspeed_pending_txs_received",
+            "newPendingTransactions notifications"
+        );
 
         let mut sigint = signal(SignalKind::interrupt()).unwrap();
         let mut sigterm = signal(SignalKind::terminate()).unwrap();
 
-        let mut timestamp_nanos = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("time went backwards")
-            .as_nanos() as u64;
+        let mut state = HeadState {
+            throughput: Throughput::new(self.config.throughput_clock, &self.config.rolling_windows)
+                .with_sub_second_blocks(self.config.chain.is_l2()),
+            chain: CanonicalChain::new(),
+        };
+
+        self.set_state(ConnectionState::Connecting, None);
 
         loop {
-            tokio::select! {
-                client = WsClientBuilder::default().build(&self.uri) => {
+            // HTTP-only endpoints are always polled, others only while their WebSocket is down
+            let poll = if self.endpoint.is_http() {
+                Some((self.endpoint.url.as_str(), None))
+            } else {
+                self.endpoint
+                    .http_url
+                    .as_deref()
+                    .filter(|_| backoff.failures() >= WS_FAILURES_BEFORE_POLLING)
+                    .map(|url| (url, Some(POLL_BEFORE_WS_RETRY)))
+            };
+            if let Some((url, until)) = poll {
+                self.set_state(ConnectionState::Polling, None);
+                tokio::select! {
+                    res = self.poll_heads(url, until, &mut state, sink.as_ref()) => {
+                        res?;
+                        info!("retrying WebSocket at {}", self.endpoint.url);
+                        backoff.reset();
+                        self.set_state(ConnectionState::Connecting, None);
+                        continue;
+                    }
+
+                    _ = sigint.recv() => {
+                        info!("received sigint, shutting down");
+                        break;
+                    }
+
+                    _ = sigterm.recv() => {
+                        info!("received sigterm, shutting down");
+                        break;
+                    }
+                }
+            }
+
+            let delay = tokio::select! {
+                client = WsClientBuilder::default().build(&self.endpoint.url) => {
                     match client {
                         Ok(client) => {
-                            info!("connected to WebSocket at {}", self.uri);
-                            reconnect_attempts = 0; // reset on successful connection
-                            backoff_delay = tokio::time::Duration::from_secs(1); // reset on successful connection
+                            info!("connected to WebSocket at {}", self.endpoint.url);
 
                             match client.subscribe(
                                 "eth_subscribe",
@@ -51,46 +185,59 @@ impl WsConnection {
                             ).await {
                                 Ok(mut subscription) => {
                                     info!("subscribed to newHeads");
+                                    self.set_state(ConnectionState::Subscribed, None);
+                                    let mut pending_txs = self.subscribe_to_pending_txs(&client).await;
+                                    // only a head proves the endpoint healthy, one that accepts
+                                    // the subscription and drops it keeps backing off
+                                    let mut healthy = false;
 
-                                    loop {
+                                    let reason = loop {
+                                        // a pending subscription that ended while the connection
+                                        // is fine (e.g. it fell behind) is picked up again
+                                        if pending_txs.as_ref().is_some_and(JoinHandle::is_finished) {
+                                            info!("resubscribing to newPendingTransactions");
+                                            pending_txs = self.subscribe_to_pending_txs(&client).await;
+                                        }
                                         match subscription.next().await {
                                             Some(Ok(header_value)) => {
+                                                if !healthy {
+                                                    healthy = true;
+                                                    backoff.reset();
+                                                }
                                                 if let Err(e) = self.process_block_header(
                                                     &client,
                                                     header_value,
-                                                    &mut timestamp_nanos,
-                                                    &output
+                                                    &mut state,
+                                                    sink.as_ref()
                                                 ).await {
                                                     error!("failed to process block header: {}", e);
-                                                    break; // break to trigger reconnection
+                                                    break format!("failed to process block header: {}", e);
                                                 }
                                             },
                                             Some(Err(e)) => {
                                                 error!("subscription error: {:?}", e);
-                                                break; // break to trigger reconnection
+                                                break format!("subscription error: {}", e);
                                             }
                                             None => {
                                                 info!("subscription closed");
-                                                break; // break to trigger reconnection
+                                                break "subscription closed".to_string();
                                             }
                                         }
+                                    };
+                                    if let Some(pending_txs) = pending_txs {
+                                        pending_txs.abort();
                                     }
+                                    self.record_failure(&mut backoff, reason)
                                 }
                                 Err(e) => {
                                     error!("failed to subscribe to newHeads: {}", e);
-                                    // falls through to trigger reconnection
+                                    self.record_failure(&mut backoff, format!("failed to subscribe to newHeads: {}", e))
                                 }
                             }
                         }
                         Err(e) => {
                             error!("failed to connect to WebSocket: {}", e);
-                            if reconnect_attempts >= MAX_RECONNECT_ATTEMPTS {
-                                return Err(eyre::eyre!("maximum reconnect attempts reached"));
-                            }
-                            tokio::time::sleep(backoff_delay).await;
-                            backoff_delay = std::cmp::min(backoff_delay * 2, tokio::time::Duration::from_secs(30));
-                            reconnect_attempts += 1;
-                            info!("reconnecting in {} seconds...", backoff_delay.as_secs());
+                            self.record_failure(&mut backoff, format!("failed to connect to WebSocket: {}", e))
                         }
                     }
                 }
@@ -100,6 +247,20 @@ impl WsConnection {
                     break;
                 }
 
+                _ = sigterm.recv() => {
+                    info!("received sigterm, shutting down");
+                    break;
+                }
+            };
+
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+
+                _ = sigint.recv() => {
+                    info!("received sigint, shutting down");
+                    break;
+                }
+
                 _ = sigterm.recv() => {
                     info!("received sigterm, shutting down");
                     break;
@@ -107,143 +268,773 @@ impl WsConnection {
             }
         }
 
+        self.set_state(ConnectionState::Stopped, None);
         Ok(())
     }
 
-    async fn process_block_header(
+    /// Pending transactions are optional, heads are traced without them when the node refuses
+    /// the subscription. Notifications are drained by their own task so they don't pile up
+    /// while a head waits on RPC calls, which would get the subscription closed.
+    async fn subscribe_to_pending_txs<C: SubscriptionClientT + Sync>(
         &self,
-        client: &WsClient,
-        header_value: Value,
-        timestamp_nanos: &mut u64,
-        output: &Output,
+        client: &C,
+    ) -> Option<JoinHandle<()>> {
+        let mempool = Arc::clone(self.mempool.as_ref()?);
+        let mut subscription: Subscription<Value> = match client
+            .subscribe(
+                "eth_subscribe",
+                jsonrpsee::core::rpc_params!["newPendingTransactions"],
+                "eth_unsubscribe",
+            )
+            .await
+        {
+            Ok(subscription) => subscription,
+            Err(e) => {
+                warn!("failed to subscribe to newPendingTransactions: {}", e);
+                return None;
+            }
+        };
+        info!("subscribed to newPendingTransactions");
+
+        let endpoint = self.endpoint.name.clone();
+        let task = async move {
+            while let Some(tx) = subscription.next().await {
+                match tx.map(serde_json::from_value::<B256>) {
+                    Ok(Ok(hash)) => {
+                        counter!("blockspeed_pending_txs_received", "endpoint" => endpoint.clone())
+                            .increment(1);
+                        mempool.record_pending(hash, now_nanos());
+                    }
+                    Ok(Err(e)) => warn!("unexpected pending transaction notification: {}", e),
+                    Err(e) => {
+                        warn!("pending transaction subscription error: {:?}", e);
+                        return;
+                    }
+                }
+            }
+            warn!("pending transaction subscription closed");
+        };
+        Some(tokio::spawn(task.in_current_span()))
+    }
+
+    /// Counts a failed connect or subscribe against the reconnect policy and returns how long
+    /// to wait before the next attempt.
+    fn record_failure(&self, backoff: &mut Backoff, error: String) -> Duration {
+        counter!("blockspeed_reconnects", "endpoint" => self.endpoint.name.clone()).increment(1);
+        let retry = backoff.fail();
+        if retry.open {
+            warn!(
+                "{} failed {} times in a row, marking it down for {}s",
+                self.endpoint.name,
+                backoff.failures(),
+                retry.delay.as_secs()
+            );
+            self.set_state(ConnectionState::Down, Some(error));
+        } else {
+            info!("reconnecting in {}ms...", retry.delay.as_millis());
+            self.set_state(ConnectionState::Reconnecting, Some(error));
+        }
+        retry.delay
+    }
+
+    /// Fetches a header by number or hash, see [`WsConnection::decode_header`].
+    async fn fetch_header<C: ClientT + Sync>(
+        &self,
+        client: &C,
+        method: &str,
+        params: ArrayParams,
+        block: &str,
+    ) -> eyre::Result<RpcHeader> {
+        let value: Value = self
+            .request_until_available(client, method, params, block)
+            .await?;
+        Ok(self.decode_header(value)?)
+    }
+
+    /// Malformed headers are counted and logged here, callers only decide whether to skip them.
+    fn decode_header(&self, value: Value) -> Result<RpcHeader, MalformedHeader> {
+        RpcHeader::decode(value).inspect_err(|e| {
+            counter!("blockspeed_malformed_headers", "endpoint" => self.endpoint.name.clone())
+                .increment(1);
+            error!("{}", e);
+        })
+    }
+
+    fn set_state(&self, state: ConnectionState, error: Option<String>) {
+        self.status.set_state(&self.endpoint.name, state, error);
+    }
+
+    /// Walks `from..=to` by number over HTTP or WS and writes the same metrics as live blocks.
+    /// Rates always come from the header timestamps and points are written at block time.
+    pub async fn replay(&self, from: u64, to: u64, sink: Arc<dyn MetricsSink>) -> eyre::Result<()> {
+        if self.endpoint.is_http() {
+            let client = HttpClientBuilder::default()
+                .build(&self.endpoint.url)
+                .map_err(|e| eyre::eyre!("failed to create HTTP client: {}", e))?;
+            self.replay_with(&client, from, to, sink.as_ref()).await
+        } else {
+            let client = WsClientBuilder::default()
+                .build(&self.endpoint.url)
+                .await
+                .map_err(|e| eyre::eyre!("failed to connect to WebSocket: {}", e))?;
+            self.replay_with(&client, from, to, sink.as_ref()).await
+        }
+    }
+
+    async fn replay_with<C: ClientT + Sync>(
+        &self,
+        client: &C,
+        from: u64,
+        to: u64,
+        sink: &dyn MetricsSink,
     ) -> eyre::Result<()> {
-        let prev_timestamp_nanos = *timestamp_nanos;
-        *timestamp_nanos = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("time went backwards")
-            .as_nanos() as u64;
+        info!("replaying blocks {}..={}", from, to);
 
-        let diff_timestamp_nanos = *timestamp_nanos - prev_timestamp_nanos;
+        // arrival times mean nothing for historical blocks, measure against the headers
+        let mut state = HeadState {
+            throughput: Throughput::new(ThroughputClock::Block, &self.config.rolling_windows)
+                .with_sub_second_blocks(self.config.chain.is_l2()),
+            chain: CanonicalChain::new(),
+        };
 
-        let header: Value = header_value;
-        let block_hash = header["hash"].as_str().unwrap_or_default();
-        let mut retries = 0;
+        let mut blocks = stream::iter(from..=to)
+            .map(|number| async move {
+                self.request_until_available::<_, Value>(
+                    client,
+                    "eth_getBlockByNumber",
+                    jsonrpsee::core::rpc_params![format!("0x{:x}", number), false],
+                    &number.to_string(),
+                )
+                .await
+            })
+            .buffered(REPLAY_PREFETCH);
 
-        let block_tx_count = match tokio::time::timeout(
-            tokio::time::Duration::from_secs(3),
-            async {
-                loop {
-                    match client.request::<Option<String>, _>(
-                        "eth_getBlockTransactionCountByHash",
-                        jsonrpsee::core::rpc_params![block_hash],
-                    )
-                    .await
-                    {
-                        Ok(Some(hex_str)) => {
-                            if retries > 0 {
-                                info!("got tx count for hash {}, retries {}", block_hash, retries)
-                            }
+        let mut replayed = 0u64;
+        while let Some(block) = blocks.next().await {
+            let Ok(block) = self.decode_header(block?) else {
+                // counted and logged, the chain can't be linked across it
+                state.chain.clear();
+                continue;
+            };
+            if self
+                .link_to_chain(client, &block, BlockSource::Replay, &mut state, sink)
+                .await?
+            {
+                self.process_block(client, &block, BlockSource::Replay, &mut state, sink)
+                    .await?;
+            }
 
-                            return Ok(u64::from_str_radix(hex_str.trim_start_matches("0x"), 16)
-                                .map_err(|e| {
-                                    eyre::eyre!(
-                                        "failed to parse transaction count '{}': {}",
-                                        hex_str,
-                                        e
-                                    )
-                                })? as usize);
-                        }
-                        Ok(None) => {
-                            // block not available yet, retry after delay
-                            tokio::time::sleep(tokio::time::Duration::from_millis(150)).await;
-                            continue;
-                        }
-                        Err(e) => {
-                            // RPC error, retry after delay
-                            let error_str = e.to_string();
-                            if error_str.contains("Connection was closed")
-                                || error_str.contains("Internal Error")
-                                || error_str.contains("1013")
-                            {
-                                return Err(eyre::eyre!("WebSocket connection closed: {}", e));
-                            }
-                            retries = retries + 1;
-                            error!(
-                            "RPC error getting block transaction count: {} for {}, retrying in 150ms",
-                            e, block_hash
-                        );
+            replayed += 1;
+            if replayed.is_multiple_of(1000) {
+                info!("replayed {} of {} blocks", replayed, to - from + 1);
+            }
+        }
 
-                            tokio::time::sleep(tokio::time::Duration::from_millis(150)).await;
-                            continue;
-                        }
-                    }
+        info!("replayed blocks {}..={}", from, to);
+        Ok(())
+    }
+
+    /// Polls `eth_blockNumber` on `url` and feeds every new head through the same pipeline as
+    /// `newHeads` notifications, blocks skipped between two polls are backfilled. Returns once
+    /// `until` has passed, or never when it is `None`.
+    async fn poll_heads(
+        &self,
+        url: &str,
+        until: Option<Duration>,
+        state: &mut HeadState,
+        sink: &dyn MetricsSink,
+    ) -> eyre::Result<()> {
+        let client = HttpClientBuilder::default()
+            .build(url)
+            .map_err(|e| eyre::eyre!("failed to create HTTP client: {}", e))?;
+        info!(
+            "polling {} every {}ms",
+            url,
+            self.config.poll_interval.as_millis()
+        );
+
+        let started = Instant::now();
+        let mut interval = tokio::time::interval(self.config.poll_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+            if until.is_some_and(|until| started.elapsed() >= until) {
+                return Ok(());
+            }
+
+            let latest = match client
+                .request::<U64, _>("eth_blockNumber", jsonrpsee::core::rpc_params![])
+                .await
+            {
+                Ok(latest) => latest.to::<u64>(),
+                Err(e) => {
+                    error!("failed to poll eth_blockNumber: {}", e);
+                    continue;
                 }
+            };
+            if state.chain.tip().is_some_and(|tip| latest <= tip.number) {
+                continue;
             }
-        ).await {
-            Ok(res) => res?,
-            Err(_) => {
-                return Err(eyre::eyre!("timeout 1s getting block transaction count for hash {}", block_hash));
+
+            let header: Value = match self
+                .request_until_available(
+                    &client,
+                    "eth_getBlockByNumber",
+                    jsonrpsee::core::rpc_params![format!("0x{:x}", latest), false],
+                    &latest.to_string(),
+                )
+                .await
+            {
+                Ok(header) => header,
+                Err(e) => {
+                    error!("failed to fetch polled block {}: {}", latest, e);
+                    continue;
+                }
+            };
+
+            if let Err(e) = self
+                .process_block_header(&client, header, state, sink)
+                .await
+            {
+                error!("failed to process polled block {}: {}", latest, e);
             }
-        };
+        }
+    }
 
-        let block_gas_used_hex = header["gasUsed"].as_str().unwrap_or_default();
-        let block_gas_used = u64::from_str_radix(block_gas_used_hex.trim_start_matches("0x"), 16)
-            .map_err(|e| eyre::eyre!("failed to parse gas used: {}", e))?;
+    async fn process_block_header<C: ClientT + Sync>(
+        &self,
+        client: &C,
+        header_value: Value,
+        state: &mut HeadState,
+        sink: &dyn MetricsSink,
+    ) -> eyre::Result<()> {
+        counter!("blockspeed_headers_received", "endpoint" => self.endpoint.name.clone())
+            .increment(1);
+        let started = Instant::now();
+        let result = self
+            .handle_block_header(client, header_value, state, sink)
+            .await;
+        // includes backfilling, reorg handling and the sink write
+        histogram!("blockspeed_header_processing_seconds", "endpoint" => self.endpoint.name.clone())
+            .record(started.elapsed().as_secs_f64());
+        result
+    }
 
-        let mgas =
-            block_gas_used as f64 / (diff_timestamp_nanos as f64 / 1_000_000_000.0) / 1_000_000.0;
+    async fn handle_block_header<C: ClientT + Sync>(
+        &self,
+        client: &C,
+        header_value: Value,
+        state: &mut HeadState,
+        sink: &dyn MetricsSink,
+    ) -> eyre::Result<()> {
+        let arrival_nanos = now_nanos();
 
-        let tps = if diff_timestamp_nanos >= 1_000_000_000 {
-            block_tx_count as f64 / (diff_timestamp_nanos as f64 / 1_000_000_000.0)
-        } else {
-            block_tx_count as f64
+        // a malformed head is counted and skipped, the next one backfills around it
+        let Ok(header) = self.decode_header(header_value) else {
+            return Ok(());
         };
+        let block_number = header.number();
+        self.status.record_head(&self.endpoint.name);
 
-        if let Err(e) = output
-            .write_mgas(&self.uri, block_hash, mgas, *timestamp_nanos)
-            .await
-        {
-            error!("failed to write mgas: {}", e);
+        if let Some(tip_number) = state.chain.tip().map(|tip| tip.number) {
+            if block_number > tip_number + 1 {
+                self.backfill(client, tip_number + 1, block_number - 1, state, sink)
+                    .await?;
+            }
         }
 
-        if let Err(e) = output
-            .write_tps(&self.uri, block_hash, tps, *timestamp_nanos)
-            .await
+        if !self
+            .link_to_chain(
+                client,
+                &header,
+                BlockSource::Live { arrival_nanos },
+                state,
+                sink,
+            )
+            .await?
         {
-            error!("failed to write tps: {}", e);
+            return Ok(());
         }
 
-        if let Err(e) = output
-            .write_gas_used(
-                &self.uri,
-                block_hash,
-                block_gas_used as f64,
-                *timestamp_nanos,
-            )
-            .await
-        {
-            error!("failed to write gas used: {}", e);
+        self.process_block(
+            client,
+            &header,
+            BlockSource::Live { arrival_nanos },
+            state,
+            sink,
+        )
+        .await
+    }
+
+    /// Fetches the blocks `from..=to` that never arrived as `newHeads` notifications. Only the
+    /// most recent `max_backfill` of them are fetched, the rest are counted as skipped.
+    async fn backfill<C: ClientT + Sync>(
+        &self,
+        client: &C,
+        from: u64,
+        to: u64,
+        state: &mut HeadState,
+        sink: &dyn MetricsSink,
+    ) -> eyre::Result<()> {
+        let missing = to - from + 1;
+        let skipped = missing.saturating_sub(self.config.max_backfill);
+        if skipped > 0 {
+            warn!(
+                "skipping {} missed blocks {}..={}",
+                skipped,
+                from,
+                from + skipped - 1
+            );
+            counter!("blockspeed_blocks_skipped", "endpoint" => self.endpoint.name.clone())
+                .increment(skipped);
+            // the parent links across the skipped blocks are lost
+            state.chain.clear();
         }
 
-        if let Err(e) = output
-            .write_txs(
-                &self.uri,
-                block_hash,
-                block_tx_count as f64,
-                *timestamp_nanos,
-            )
-            .await
-        {
-            error!("failed to write txs: {}", e);
+        for number in from + skipped..=to {
+            info!("backfilling missed block {}", number);
+            let block = match self
+                .fetch_header(
+                    client,
+                    "eth_getBlockByNumber",
+                    jsonrpsee::core::rpc_params![format!("0x{:x}", number), false],
+                    &number.to_string(),
+                )
+                .await
+            {
+                Ok(block) => block,
+                Err(e) if e.is::<MalformedHeader>() => {
+                    state.chain.clear();
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            if !self
+                .link_to_chain(client, &block, BlockSource::Backfill, state, sink)
+                .await?
+            {
+                continue;
+            }
+            self.process_block(client, &block, BlockSource::Backfill, state, sink)
+                .await?;
+            counter!("blockspeed_blocks_backfilled", "endpoint" => self.endpoint.name.clone())
+                .increment(1);
+        }
+
+        Ok(())
+    }
+
+    /// Checks that `block` extends the canonical tip. If it doesn't, walks back to the common
+    /// ancestor, records the reorg and re-emits the new canonical blocks in between. Returns
+    /// `false` for a block that is already part of the chain window.
+    async fn link_to_chain<C: ClientT + Sync>(
+        &self,
+        client: &C,
+        block: &RpcHeader,
+        source: BlockSource,
+        state: &mut HeadState,
+        sink: &dyn MetricsSink,
+    ) -> eyre::Result<bool> {
+        let block_hash = block.hash();
+        let parent_hash = block.parent_hash();
+
+        if state.chain.find(&block_hash).is_some() {
+            return Ok(false);
+        }
+        if !state.chain.forks_from_tip(&parent_hash) {
+            return Ok(true);
         }
 
-        if let Err(e) = output
-            .write_block_per_sec(&self.uri, 1.0, *timestamp_nanos)
+        let new_head = block.number();
+
+        let mut reemitted: Vec<RpcHeader> = Vec::new();
+        let mut ancestor_hash = parent_hash;
+        let common_ancestor = loop {
+            if let Some(ancestor) = state.chain.find(&ancestor_hash) {
+                break Some(ancestor.clone());
+            }
+            if reemitted.len() >= CHAIN_WINDOW {
+                break None;
+            }
+
+            let parent = match self
+                .fetch_header(
+                    client,
+                    "eth_getBlockByHash",
+                    jsonrpsee::core::rpc_params![&ancestor_hash, false],
+                    &ancestor_hash,
+                )
+                .await
+            {
+                Ok(parent) => parent,
+                Err(e) if e.is::<MalformedHeader>() => break None,
+                Err(e) => return Err(e),
+            };
+            ancestor_hash = parent.parent_hash();
+            reemitted.push(parent);
+        };
+
+        let Some(common_ancestor) = common_ancestor else {
+            warn!(
+                "no common ancestor for {} within {} blocks, resetting chain",
+                block_hash, CHAIN_WINDOW
+            );
+            state.chain.clear();
+            return Ok(true);
+        };
+
+        let orphaned = state.chain.truncate_after(common_ancestor.number);
+        state
+            .throughput
+            .rewind(common_ancestor.number, common_ancestor.block_timestamp);
+        reemitted.reverse();
+
+        let reorg = Reorg {
+            common_ancestor,
+            orphaned,
+            reemitted: reemitted.iter().map(RpcHeader::hash).collect(),
+            new_head,
+        };
+        warn!(
+            "reorg of depth {} at block {}, new head {}",
+            reorg.depth(),
+            reorg.common_ancestor.number,
+            block_hash
+        );
+        counter!("blockspeed_reorgs", "endpoint" => self.endpoint.name.clone()).increment(1);
+
+        let timestamp_nanos = now_nanos();
+        if let Err(e) = sink
+            .write_reorg(&self.endpoint.name, &reorg, timestamp_nanos)
             .await
         {
-            error!("failed to write block per sec: {}", e);
+            error!("failed to write reorg: {}", e);
+        }
+
+        // re-emitted blocks keep the source of the block that revealed the reorg, so replayed
+        // ranges aren't marked as backfilled
+        let source = match source {
+            BlockSource::Replay => BlockSource::Replay,
+            _ => BlockSource::Backfill,
+        };
+        for block in &reemitted {
+            self.process_block(client, block, source, state, sink)
+                .await?;
+        }
+
+        Ok(true)
+    }
+
+    async fn process_block<C: ClientT + Sync>(
+        &self,
+        client: &C,
+        header: &RpcHeader,
+        source: BlockSource,
+        state: &mut HeadState,
+        sink: &dyn MetricsSink,
+    ) -> eyre::Result<()> {
+        let block_hash = header.hash();
+        let block_hash = block_hash.as_str();
+
+        let block_gas_used = header.header.gas_used;
+        let block_number = header.number();
+        let block_timestamp = header.header.timestamp;
+
+        // backfilled points are written at their block time rather than when they were fetched
+        let timestamp_nanos = match source {
+            BlockSource::Live { arrival_nanos } => arrival_nanos,
+            BlockSource::Backfill | BlockSource::Replay => {
+                block_point_nanos(block_timestamp, block_number)
+            }
+        };
+
+        // record the announcement before any RPC round trip skews the arrival time
+        let delay = match (&self.propagation, source) {
+            (Some(propagation), BlockSource::Live { arrival_nanos }) => Some(propagation.announce(
+                block_hash,
+                block_number,
+                &self.endpoint.name,
+                arrival_nanos,
+            )),
+            _ => None,
+        };
+
+        let chain = self.config.chain;
+        let mut stats = None;
+        let mut tx_hashes: Vec<B256> = Vec::new();
+        let mut l1_origin = match chain {
+            Chain::Arbitrum => L1Origin::from_arbitrum_header(&header.l2),
+            _ => None,
+        };
+        let (block_tx_count, system_tx_count) = match self.config.block_fetch {
+            // blocks fetched by number already carry their transaction hashes
+            BlockFetch::Header if !chain.is_l2() => match &header.transactions {
+                Some(transactions) => {
+                    tx_hashes.clone_from(transactions);
+                    (transactions.len(), 0)
+                }
+                // inclusion latency needs the hashes rather than just their count
+                None if self.mempool.is_some() => {
+                    let block = self
+                        .fetch_header(
+                            client,
+                            "eth_getBlockByHash",
+                            jsonrpsee::core::rpc_params![block_hash, false],
+                            block_hash,
+                        )
+                        .await?;
+                    tx_hashes = block.transactions.unwrap_or_default();
+                    (tx_hashes.len(), 0)
+                }
+                None => {
+                    let count: U64 = self
+                        .request_until_available(
+                            client,
+                            "eth_getBlockTransactionCountByHash",
+                            jsonrpsee::core::rpc_params![block_hash],
+                            block_hash,
+                        )
+                        .await?;
+                    (count.to::<usize>(), 0)
+                }
+            },
+            // L2 system transactions can only be told apart by their type
+            BlockFetch::Header | BlockFetch::Full => {
+                let mut block: FullBlock = self
+                    .request_until_available(
+                        client,
+                        "eth_getBlockByHash",
+                        jsonrpsee::core::rpc_params![block_hash, true],
+                        block_hash,
+                    )
+                    .await?;
+
+                if chain == Chain::OpStack {
+                    l1_origin = op_l1_origin(block.transactions.first());
+                }
+                tx_hashes = block.transactions.iter().map(|tx| tx.hash).collect();
+                let total = block.transactions.len();
+                block
+                    .transactions
+                    .retain(|tx| !chain.is_system_tx(tx.tx_type.to::<u64>()));
+
+                if self.config.block_fetch == BlockFetch::Full {
+                    let base_fee = header.header.base_fee_per_gas.unwrap_or_default() as u128;
+                    let mut block_stats = BlockStats::default();
+                    block_stats.add_transactions(&block.transactions, base_fee);
+                    stats = Some(block_stats);
+                }
+                (block.transactions.len(), total - block.transactions.len())
+            }
+            BlockFetch::Receipts => {
+                let mut receipts: Vec<BlockReceipt> = self
+                    .request_until_available(
+                        client,
+                        "eth_getBlockReceipts",
+                        jsonrpsee::core::rpc_params![block_hash],
+                        block_hash,
+                    )
+                    .await?;
+
+                // receipts lack the calldata the L1 origin is encoded in
+                if chain == Chain::OpStack {
+                    let first: BlockTransaction = self
+                        .request_until_available(
+                            client,
+                            "eth_getTransactionByBlockHashAndIndex",
+                            jsonrpsee::core::rpc_params![block_hash, "0x0"],
+                            block_hash,
+                        )
+                        .await?;
+                    l1_origin = op_l1_origin(Some(&first));
+                }
+                tx_hashes = receipts
+                    .iter()
+                    .map(|receipt| receipt.transaction_hash)
+                    .collect();
+                let total = receipts.len();
+                receipts.retain(|receipt| !chain.is_system_tx(receipt.tx_type.to::<u64>()));
+
+                let base_fee = header.header.base_fee_per_gas.unwrap_or_default() as u128;
+                let mut block_stats = BlockStats::default();
+                block_stats.add_receipts(&receipts, base_fee);
+                stats = Some(block_stats);
+                (receipts.len(), total - receipts.len())
+            }
+        };
+
+        let rates = match source {
+            BlockSource::Live { arrival_nanos } => state.throughput.record(
+                arrival_nanos,
+                block_number,
+                block_timestamp,
+                block_gas_used,
+                block_tx_count as u64,
+            ),
+            BlockSource::Backfill | BlockSource::Replay => state.throughput.record_backfilled(
+                block_number,
+                block_timestamp,
+                block_gas_used,
+                block_tx_count as u64,
+            ),
+        };
+        let fee_recipient = format!("{:#x}", header.header.beneficiary);
+        // L2 extraData is chain data, e.g. the Arbitrum send root, rather than a builder
+        let builder = if chain.is_l2() {
+            None
+        } else {
+            builder_tag(&header.header.extra_data)
+        };
+        state.chain.push(ChainBlock {
+            number: block_number,
+            hash: block_hash.to_string(),
+            parent_hash: header.parent_hash(),
+            block_timestamp,
+            timestamp_nanos,
+            fee_recipient: fee_recipient.clone(),
+            builder: builder.clone(),
+        });
+
+        let mempool = self.mempool.as_ref().and_then(|mempool| match source {
+            BlockSource::Live { arrival_nanos } => {
+                Some(mempool.record_block(&tx_hashes, arrival_nanos))
+            }
+            BlockSource::Backfill | BlockSource::Replay => {
+                mempool.forget(&tx_hashes);
+                None
+            }
+        });
+
+        let metrics = BlockMetrics {
+            endpoint: self.endpoint.name.clone(),
+            number: block_number,
+            hash: block_hash.to_string(),
+            block_timestamp,
+            timestamp_nanos,
+            backfilled: matches!(source, BlockSource::Backfill),
+            gas_used: block_gas_used,
+            tx_count: block_tx_count as u64,
+            system_tx_count: system_tx_count as u64,
+            l1_origin,
+            fee_recipient,
+            builder,
+            rates,
+            rolling: state.throughput.rolling().collect(),
+            propagation: delay,
+            mempool,
+            fee_market: fee_market(
+                header,
+                block_gas_used,
+                self.config.blob_base_fee_update_fraction,
+            ),
+            stats,
+        };
+
+        if let Err(e) = sink.write_blocks(std::slice::from_ref(&metrics)).await {
+            error!("failed to write block metrics: {}", e);
         }
 
         Ok(())
     }
+
+    /// Calls `method` until the node returns a non-null result, which covers the race between a
+    /// `newHeads` notification and the block data being queryable.
+    async fn request_until_available<C: ClientT + Sync, T: DeserializeOwned>(
+        &self,
+        client: &C,
+        method: &str,
+        params: ArrayParams,
+        block_hash: &str,
+    ) -> eyre::Result<T> {
+        let mut retries = 0;
+
+        match tokio::time::timeout(tokio::time::Duration::from_secs(3), async {
+            loop {
+                match client.request::<Option<T>, _>(method, params.clone()).await {
+                    Ok(Some(res)) => {
+                        if retries > 0 {
+                            info!(
+                                "got {} for hash {}, retries {}",
+                                method, block_hash, retries
+                            )
+                        }
+                        return Ok(res);
+                    }
+                    Ok(None) => {
+                        // block not available yet, retry after delay
+                        counter!(
+                            "blockspeed_rpc_retries",
+                            "endpoint" => self.endpoint.name.clone(),
+                            "method" => method.to_string()
+                        )
+                        .increment(1);
+                        tokio::time::sleep(tokio::time::Duration::from_millis(150)).await;
+                        continue;
+                    }
+                    Err(e) => {
+                        // RPC error, retry after delay
+                        let error_str = e.to_string();
+                        if error_str.contains("Connection was closed")
+                            || error_str.contains("Internal Error")
+                            || error_str.contains("1013")
+                        {
+                            return Err(eyre::eyre!("WebSocket connection closed: {}", e));
+                        }
+                        retries += 1;
+                        counter!(
+                            "blockspeed_rpc_retries",
+                            "endpoint" => self.endpoint.name.clone(),
+                            "method" => method.to_string()
+                        )
+                        .increment(1);
+                        error!(
+                            "RPC error calling {}: {} for {}, retrying in 150ms",
+                            method, e, block_hash
+                        );
+
+                        tokio::time::sleep(tokio::time::Duration::from_millis(150)).await;
+                        continue;
+                    }
+                }
+            }
+        })
+        .await
+        {
+            Ok(res) => res,
+            Err(_) => Err(eyre::eyre!(
+                "timeout 3s calling {} for hash {}",
+                method,
+                block_hash
+            )),
+        }
+    }
+}
+
+fn now_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time went backwards")
+        .as_nanos() as u64
+}
+
+/// The OP Stack L1 attributes deposit transaction always comes first in a block.
+fn op_l1_origin(first: Option<&BlockTransaction>) -> Option<L1Origin> {
+    first
+        .filter(|tx| Chain::OpStack.is_system_tx(tx.tx_type.to::<u64>()))
+        .and_then(|tx| L1Origin::from_op_attributes(&tx.input))
+}
+
+/// A block before London has no base fee and one before Cancun no blob gas.
+fn fee_market(header: &RpcHeader, gas_used: u64, blob_base_fee_update_fraction: u64) -> FeeMarket {
+    let header = &header.header;
+    FeeMarket::new(
+        gas_used,
+        header.gas_limit,
+        header.base_fee_per_gas.map(u128::from),
+        header.blob_gas_used,
+        header.excess_blob_gas,
+        blob_base_fee_update_fraction,
+    )
 }